@@ -0,0 +1,92 @@
+/* runs day 9 and a doubling program through the library, see the Makefile */
+#include <intcode.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+static int failed = 0;
+
+#define CHECK(cond)                                                  \
+    do {                                                             \
+        if (!(cond)) {                                               \
+            fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #cond); \
+            failed = 1;                                              \
+        }                                                            \
+    } while (0)
+
+/* the comma separated program in path, its length in len */
+static int64_t *load(const char *path, size_t *len) {
+    FILE *f = fopen(path, "r");
+    if (!f) {
+        return NULL;
+    }
+    size_t cap = 1024;
+    int64_t *prog = malloc(cap * sizeof *prog);
+    long long v;
+    *len = 0;
+    while (fscanf(f, "%lld,", &v) == 1) {
+        if (*len == cap) {
+            cap *= 2;
+            prog = realloc(prog, cap * sizeof *prog);
+        }
+        prog[(*len)++] = v;
+    }
+    fclose(f);
+    return prog;
+}
+
+static void doubler(void) {
+    /* outputs twice each input until it reads a 0 */
+    const int64_t prog[] = {3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0};
+    Intcode *m = intcode_new(prog, sizeof prog / sizeof prog[0]);
+    int64_t v = 0;
+
+    CHECK(intcode_run(m) == INTCODE_NEEDS_INPUT);
+    CHECK(intcode_pop_output(m, &v) == 0);
+    intcode_push_input(m, 21);
+    CHECK(intcode_run(m) == INTCODE_NEEDS_INPUT);
+    CHECK(intcode_pop_output(m, &v) == 1 && v == 42);
+
+    /* triples from now on */
+    intcode_write(m, 7, 3);
+    CHECK(intcode_read(m, 7, &v) == 0 && v == 3);
+    intcode_push_input(m, 5);
+    intcode_push_input(m, 0);
+    CHECK(intcode_run(m) == INTCODE_HALTED);
+    CHECK(intcode_pop_output(m, &v) == 1 && v == 15);
+    CHECK(intcode_error(m) == NULL);
+
+    intcode_write(m, 14, 98);
+    CHECK(intcode_run(m) == INTCODE_ERROR);
+    CHECK(strcmp(intcode_error(m), "unknown opcode '98' at '14'") == 0);
+    intcode_free(m);
+}
+
+static void day9(void) {
+    size_t len;
+    int64_t *prog = load("../resources/day9-input.txt", &len);
+    CHECK(prog != NULL);
+    if (!prog) {
+        return;
+    }
+    Intcode *m = intcode_new(prog, len);
+    free(prog);
+    int64_t v = 0;
+
+    intcode_push_input(m, 1);
+    CHECK(intcode_run(m) == INTCODE_HALTED);
+    CHECK(intcode_pop_output(m, &v) == 1 && v == 3063082071LL);
+    CHECK(intcode_pop_output(m, &v) == 0);
+    intcode_free(m);
+}
+
+int main(void) {
+    CHECK(intcode_new(NULL, 0) == NULL);
+    intcode_free(NULL);
+    doubler();
+    day9();
+    if (!failed) {
+        printf("ok\n");
+    }
+    return failed;
+}
@@ -0,0 +1,52 @@
+/* The intcode cpu of this repo as a C library.
+ *
+ *     cargo build --lib
+ *     cc -Iinclude prog.c -Ltarget/debug -lintcode
+ *
+ * Values cross as 64 bit integers. A machine runs until it halts, wants an
+ * input nobody pushed or fails, keeping its outputs for intcode_pop_output.
+ */
+#ifndef INTCODE_H
+#define INTCODE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define INTCODE_HALTED 0
+#define INTCODE_NEEDS_INPUT 1
+#define INTCODE_ERROR -1
+
+typedef struct Intcode Intcode;
+
+/* a machine running a copy of the program, NULL when program is NULL */
+Intcode *intcode_new(const int64_t *program, size_t len);
+
+void intcode_push_input(Intcode *machine, int64_t value);
+
+/* INTCODE_HALTED, INTCODE_NEEDS_INPUT or INTCODE_ERROR */
+int intcode_run(Intcode *machine);
+
+/* 1 with the oldest output taken into value, 0 when there is none, -1 when
+ * it doesn't fit in 64 bits */
+int intcode_pop_output(Intcode *machine, int64_t *value);
+
+/* 0 with the cell read into value, -1 when it doesn't fit in 64 bits */
+int intcode_read(const Intcode *machine, uint64_t addr, int64_t *value);
+
+void intcode_write(Intcode *machine, uint64_t addr, int64_t value);
+
+/* the message of the last error or NULL, valid until the next intcode_run */
+const char *intcode_error(const Intcode *machine);
+
+/* NULL is fine */
+void intcode_free(Intcode *machine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use cpu::{Cpu, Step};
+use intcode::cpu;
+
+// cargo run --bin calls -- resources/day9-input.txt 2
+// runs a program on the given inputs and shows the functions it called and
+// where it was when it stopped: halted, out of inputs or on an error
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: calls <program> [inputs ...]");
+    }
+
+    let prog = cpu::parse_input(&args[1])?;
+    let mut cpu = Cpu::new_detached(&prog);
+    cpu.track_calls();
+    for v in args[2..].iter() {
+        cpu.push_input(v.parse()?);
+    }
+
+    let stop = loop {
+        match cpu.step() {
+            Ok(Step::Halt) => break "halted".to_string(),
+            Ok(Step::NeedInput) => break "out of inputs".to_string(),
+            Ok(_) => {}
+            Err(e) => break e.to_string(),
+        }
+    };
+
+    let calls = cpu.calls().unwrap();
+    print!("{}", calls.tree());
+    println!(
+        "{} functions, {} calls deep at most",
+        calls.functions().len(),
+        calls.max_depth
+    );
+    println!("{} after {} instructions", stop, cpu.count());
+    print!("{}", calls.backtrace(cpu.pc()));
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+mod compiler;
+use intcode::cpu;
+
+// cargo run --bin compile -- program.src [output]
+// without an output the program runs with the numbers read from stdin
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: compile <source> [output]");
+    }
+
+    let src = std::fs::read_to_string(&args[1])?;
+    let prog = compiler::compile(&src)?;
+
+    if let Some(path) = args.get(2) {
+        let cells: Vec<String> = prog.iter().map(|v| v.to_string()).collect();
+        std::fs::write(path, cells.join(","))?;
+        println!("{} cells", prog.len());
+        return Ok(());
+    }
+
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+    let inputs = input
+        .split_whitespace()
+        .map(|s| s.parse::<i128>())
+        .collect::<Result<Vec<_>, _>>()?;
+    for v in cpu::run_with_inputs(&prog, &inputs)? {
+        println!("{}", v);
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use super::parse::{Expr, Function, Op, Stmt};
+use anyhow::Result;
+use std::collections::HashMap;
+
+// a cell that is only known once its function or the whole program is laid
+// out
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    Num(i128),
+    Label(usize),
+    // temporaries of expressions, placed after the locals
+    Temp(usize),
+    // past the end of the current frame, where a callee's frame starts
+    Frame(i128),
+    // minus the size of the current frame
+    Unwind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Imm(Value),
+    Rel(Value),
+}
+
+use Operand::*;
+use Value::*;
+
+fn slot(k: i128) -> Operand {
+    Rel(Num(k))
+}
+
+fn imm(v: i128) -> Operand {
+    Imm(Num(v))
+}
+
+// A frame starts at the relative base: the return address, the return
+// value, the parameters, the locals and last the temporaries. A call moves
+// the relative base past the caller's frame and back on return.
+#[derive(Default)]
+struct Codegen {
+    code: Vec<Value>,
+    labels: Vec<Option<usize>>,
+    // entry label and arity
+    functions: HashMap<String, (usize, usize)>,
+    scopes: Vec<HashMap<String, i128>>,
+    slots: i128,
+    temps: usize,
+}
+
+impl Codegen {
+    fn emit(&mut self, opcode: i128, operands: &[Operand]) {
+        let mut inst = opcode;
+        for (n, operand) in operands.iter().enumerate() {
+            if let Rel(_) = operand {
+                inst += 2 * 10i128.pow(n as u32 + 2);
+            } else {
+                inst += 10i128.pow(n as u32 + 2);
+            }
+        }
+        self.code.push(Num(inst));
+        for operand in operands {
+            match operand {
+                Imm(v) | Rel(v) => self.code.push(*v),
+            }
+        }
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn place(&mut self, label: usize) {
+        self.labels[label] = Some(self.code.len());
+    }
+
+    fn temp(&mut self, depth: usize) -> Operand {
+        self.temps = self.temps.max(depth + 1);
+        Rel(Temp(depth))
+    }
+
+    fn copy(&mut self, from: Operand, to: Operand) {
+        self.emit(1, &[from, imm(0), to]);
+    }
+
+    fn jump(&mut self, label: usize) {
+        self.emit(5, &[imm(1), Imm(Label(label))]);
+    }
+
+    fn jump_if_zero(&mut self, cond: Operand, label: usize) {
+        self.emit(6, &[cond, Imm(Label(label))]);
+    }
+
+    fn var(&self, name: &str) -> Result<Operand> {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(k) => Ok(slot(*k)),
+            None => anyhow::bail!("unknown variable '{}'", name),
+        }
+    }
+
+    // evaluates `e` into `to`, with temporaries from `depth` on
+    fn expr(&mut self, e: &Expr, to: Operand, depth: usize) -> Result<()> {
+        match e {
+            Expr::Num(v) => self.copy(imm(*v), to),
+            Expr::Var(name) => {
+                let from = self.var(name)?;
+                self.copy(from, to);
+            }
+            Expr::Neg(a) => {
+                self.expr(a, to, depth)?;
+                self.emit(2, &[to, imm(-1), to]);
+            }
+            Expr::Not(a) => {
+                self.expr(a, to, depth)?;
+                self.emit(8, &[to, imm(0), to]);
+            }
+            Expr::Binary(op, a, b) => {
+                let (x, y) = (self.temp(depth), self.temp(depth + 1));
+                self.expr(a, x, depth + 1)?;
+                self.expr(b, y, depth + 2)?;
+                match op {
+                    Op::Add => self.emit(1, &[x, y, to]),
+                    Op::Sub => {
+                        self.emit(2, &[y, imm(-1), y]);
+                        self.emit(1, &[x, y, to]);
+                    }
+                    Op::Mul => self.emit(2, &[x, y, to]),
+                    Op::Lt => self.emit(7, &[x, y, to]),
+                    Op::Gt => self.emit(7, &[y, x, to]),
+                    Op::Eq => self.emit(8, &[x, y, to]),
+                    Op::Ne | Op::Le | Op::Ge => {
+                        match op {
+                            Op::Ne => self.emit(8, &[x, y, to]),
+                            Op::Le => self.emit(7, &[y, x, to]),
+                            _ => self.emit(7, &[x, y, to]),
+                        }
+                        self.emit(8, &[to, imm(0), to]);
+                    }
+                }
+            }
+            Expr::Call(name, args) => self.call(name, args, to, depth)?,
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], to: Operand, depth: usize) -> Result<()> {
+        match (name, args.len()) {
+            ("input", 0) => {
+                self.emit(3, &[to]);
+                return Ok(());
+            }
+            ("output", 1) => {
+                let value = self.temp(depth);
+                self.expr(&args[0], value, depth + 1)?;
+                self.emit(4, &[value]);
+                self.copy(imm(0), to);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let (entry, arity) = match self.functions.get(name) {
+            Some(f) => *f,
+            None => anyhow::bail!("unknown function '{}'", name),
+        };
+        if arity != args.len() {
+            anyhow::bail!("'{}' takes {} arguments, not {}", name, arity, args.len());
+        }
+
+        // arguments can be calls themselves, so they are all evaluated
+        // before the callee's frame is filled
+        for (n, arg) in args.iter().enumerate() {
+            let value = self.temp(depth + n);
+            self.expr(arg, value, depth + n + 1)?;
+        }
+        for n in 0..args.len() {
+            let value = self.temp(depth + n);
+            self.copy(value, Rel(Frame(2 + n as i128)));
+        }
+
+        let back = self.label();
+        self.copy(Imm(Label(back)), Rel(Frame(0)));
+        self.emit(9, &[Imm(Frame(0))]);
+        self.jump(entry);
+        self.place(back);
+        self.emit(9, &[Imm(Unwind)]);
+        self.copy(Rel(Frame(1)), to);
+        Ok(())
+    }
+
+    fn block(&mut self, body: &[Stmt]) -> Result<()> {
+        self.scopes.push(HashMap::new());
+        for stmt in body {
+            self.stmt(stmt)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<()> {
+        match stmt {
+            Stmt::Let(name, value) => {
+                let k = self.slots;
+                self.slots += 1;
+                self.expr(value, slot(k), 0)?;
+                let scope = self.scopes.last_mut().unwrap();
+                scope.insert(name.clone(), k);
+            }
+            Stmt::Assign(name, value) => {
+                let to = self.var(name)?;
+                self.expr(value, to, 0)?;
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let (other, end) = (self.label(), self.label());
+                let value = self.temp(0);
+                self.expr(cond, value, 1)?;
+                self.jump_if_zero(value, other);
+                self.block(then)?;
+                self.jump(end);
+                self.place(other);
+                self.block(otherwise)?;
+                self.place(end);
+            }
+            Stmt::While(cond, body) => {
+                let (top, end) = (self.label(), self.label());
+                self.place(top);
+                let value = self.temp(0);
+                self.expr(cond, value, 1)?;
+                self.jump_if_zero(value, end);
+                self.block(body)?;
+                self.jump(top);
+                self.place(end);
+            }
+            Stmt::Return(value) => {
+                let value = value.clone().unwrap_or(Expr::Num(0));
+                self.expr(&value, slot(1), 0)?;
+                self.emit(6, &[imm(0), slot(0)]);
+            }
+            Stmt::Expr(e) => {
+                let value = self.temp(0);
+                self.expr(e, value, 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, f: &Function) -> Result<()> {
+        let start = self.code.len();
+        let entry = self.functions[&f.name].0;
+        self.place(entry);
+
+        let params = f.params.iter().enumerate();
+        self.scopes = vec![params.map(|(n, p)| (p.clone(), 2 + n as i128)).collect()];
+        self.slots = 2 + f.params.len() as i128;
+        self.temps = 0;
+
+        self.block(&f.body)
+            .map_err(|e| anyhow::anyhow!("{} in '{}'", e, f.name))?;
+        self.stmt(&Stmt::Return(None))?;
+
+        let size = self.slots + self.temps as i128;
+        for cell in self.code[start..].iter_mut() {
+            *cell = match *cell {
+                Temp(depth) => Num(self.slots + depth as i128),
+                Frame(k) => Num(size + k),
+                Unwind => Num(-size),
+                cell => cell,
+            };
+        }
+        Ok(())
+    }
+}
+
+pub fn generate(functions: &[Function]) -> Result<Vec<i128>> {
+    let mut gen = Codegen::default();
+
+    for f in functions {
+        if f.name == "input" || f.name == "output" {
+            anyhow::bail!("'{}' is a builtin", f.name);
+        }
+        let entry = gen.label();
+        if gen
+            .functions
+            .insert(f.name.clone(), (entry, f.params.len()))
+            .is_some()
+        {
+            anyhow::bail!("'{}' is defined twice", f.name);
+        }
+    }
+    match gen.functions.get("main") {
+        Some((_, 0)) => {}
+        Some(_) => anyhow::bail!("'main' takes no arguments"),
+        None => anyhow::bail!("no 'main' function"),
+    }
+
+    // the stack starts right after the program
+    let (stack, halt) = (gen.label(), gen.label());
+    gen.emit(9, &[Imm(Label(stack))]);
+    gen.copy(Imm(Label(halt)), slot(0));
+    let main = gen.functions["main"].0;
+    gen.jump(main);
+    gen.place(halt);
+    gen.emit(99, &[]);
+
+    for f in functions {
+        gen.function(f)?;
+    }
+    gen.place(stack);
+
+    let labels = gen.labels;
+    Ok(gen
+        .code
+        .into_iter()
+        .map(|cell| match cell {
+            Num(v) => v,
+            Label(label) => labels[label].unwrap() as i128,
+            _ => unreachable!(),
+        })
+        .collect())
+}
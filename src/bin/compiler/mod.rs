@@ -0,0 +1,124 @@
+use anyhow::Result;
+
+mod codegen;
+mod parse;
+
+// Compiles a small language to Intcode:
+//
+//     fn square(x) { return x * x; }
+//     fn main() {
+//         let n = input();
+//         while n > 0 {
+//             output(square(n));
+//             n = n - 1;
+//         }
+//     }
+//
+// Values are integers, `if`/`else` and `while` take any expression and
+// treat zero as false. Functions keep their frames on a stack addressed
+// through the relative base, so they can recurse. Comments start with `//`
+// or `#`.
+pub fn compile(src: &str) -> Result<Vec<i128>> {
+    codegen::generate(&parse::parse(src)?)
+}
+
+#[cfg(test)]
+mod compiler_tests {
+    use super::*;
+    use crate::cpu;
+
+    fn run(src: &str, inputs: &[i128]) -> Vec<i128> {
+        let prog = compile(src).unwrap();
+        cpu::run_with_inputs(&prog, inputs).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let src = "
+            fn main() {
+                let a = input();
+                let b = input();
+                output(a + b * 2 - -3);
+                output(a < b);
+                output(a >= b);
+                output(a != b);
+                output(!(a == b) * 10);
+            }
+        ";
+        assert_eq!(vec![20, 1, 0, 1, 10], run(src, &[5, 6]));
+    }
+
+    #[test]
+    fn control_flow() {
+        let src = "
+            fn main() {
+                let n = input();
+                while n > 0 {
+                    if n == 2 {
+                        output(200);
+                    } else if n < 2 {
+                        let n = 100;   // shadows the counter
+                        output(n);
+                    } else {
+                        output(n);
+                    }
+                    n = n - 1;
+                }
+            }
+        ";
+        assert_eq!(vec![4, 3, 200, 100], run(src, &[4]));
+    }
+
+    #[test]
+    fn recursion() {
+        let src = "
+            fn fib(n) {
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+            fn pow(b, e) {
+                if e == 0 { return 1; }
+                return b * pow(b, e - 1);
+            }
+            fn main() {
+                output(fib(input()));
+                output(pow(fib(5), pow(2, 2)) + fib(3));
+            }
+        ";
+        assert_eq!(vec![55, 627], run(src, &[10]));
+    }
+
+    #[test]
+    fn nic_fixture() {
+        // a NIC fixture: sends `x + y` to the address it reads
+        let prog = compile(
+            "
+            fn main() {
+                let addr = input();
+                let x = input();
+                output(addr);
+                output(x);
+                output(x + input());
+            }
+            ",
+        )
+        .unwrap();
+        let outputs = cpu::run_with_inputs(&prog, &[3, 40, 2]).unwrap();
+        assert_eq!(vec![3, 40, 42], outputs);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |src| compile(src).unwrap_err().to_string();
+        assert_eq!("no 'main' function", err("fn f() {}"));
+        assert_eq!(
+            "unknown variable 'x' in 'main'",
+            err("fn main() { x = 1; }")
+        );
+        assert_eq!(
+            "'f' takes 1 arguments, not 2 in 'main'",
+            err("fn f(a) {} fn main() { f(1, 2); }")
+        );
+        assert_eq!("'f' is defined twice", err("fn f() {} fn f() {}"));
+    }
+}
@@ -0,0 +1,335 @@
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(i128),
+    Var(String),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    // `input()` and `output(e)` are calls too
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Let(String, Expr),
+    Assign(String, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(i128),
+    Ident(String),
+    // keywords and punctuation
+    Sym(&'static str),
+}
+
+const SYMBOLS: [&str; 19] = [
+    "==", "!=", "<=", ">=", "<", ">", "=", "+", "-", "*", "!", "(", ")", "{", "}", ",", ";", "//",
+    "#",
+];
+const KEYWORDS: [&str; 6] = ["fn", "let", "if", "else", "while", "return"];
+
+fn lex(src: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+
+    for (n, line) in src.lines().enumerate() {
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let len = if rest.starts_with(|c: char| c.is_ascii_digit()) {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Num(rest[..len].parse()?), n + 1));
+                len
+            } else if rest.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                let len = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                let word = &rest[..len];
+                match KEYWORDS.iter().find(|k| **k == word) {
+                    Some(k) => tokens.push((Token::Sym(k), n + 1)),
+                    None => tokens.push((Token::Ident(word.to_string()), n + 1)),
+                }
+                len
+            } else {
+                match SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+                    // comments run to the end of the line
+                    Some(&"//") | Some(&"#") => break,
+                    Some(s) => {
+                        tokens.push((Token::Sym(s), n + 1));
+                        s.len()
+                    }
+                    None => anyhow::bail!("unexpected '{}' at line {}", &rest[..1], n + 1),
+                }
+            };
+            rest = rest[len..].trim_start();
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        match self.tokens.get(self.pos) {
+            Some((_, line)) => *line,
+            None => self.tokens.last().map(|(_, line)| *line).unwrap_or(1),
+        }
+    }
+
+    fn eat(&mut self, sym: &str) -> bool {
+        match self.peek() {
+            Some(Token::Sym(s)) if *s == sym => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, sym: &str) -> Result<()> {
+        if !self.eat(sym) {
+            anyhow::bail!("expected '{}' at line {}", sym, self.line());
+        }
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.peek().cloned() {
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => anyhow::bail!("expected a name at line {}", self.line()),
+        }
+    }
+
+    fn function(&mut self) -> Result<Function> {
+        self.expect("fn")?;
+        let name = self.ident()?;
+        self.expect("(")?;
+        let mut params = vec![];
+        while !self.eat(")") {
+            if !params.is_empty() {
+                self.expect(",")?;
+            }
+            params.push(self.ident()?);
+        }
+        let body = self.block()?;
+        Ok(Function { name, params, body })
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        self.expect("{")?;
+        let mut body = vec![];
+        while !self.eat("}") {
+            if self.peek().is_none() {
+                anyhow::bail!("unclosed block at line {}", self.line());
+            }
+            body.push(self.stmt()?);
+        }
+        Ok(body)
+    }
+
+    fn stmt(&mut self) -> Result<Stmt> {
+        if self.eat("let") {
+            let name = self.ident()?;
+            self.expect("=")?;
+            let value = self.expr()?;
+            self.expect(";")?;
+            return Ok(Stmt::Let(name, value));
+        }
+        if self.eat("if") {
+            let cond = self.expr()?;
+            let then = self.block()?;
+            let otherwise = if !self.eat("else") {
+                vec![]
+            } else if self.peek() == Some(&Token::Sym("if")) {
+                vec![self.stmt()?]
+            } else {
+                self.block()?
+            };
+            return Ok(Stmt::If(cond, then, otherwise));
+        }
+        if self.eat("while") {
+            let cond = self.expr()?;
+            return Ok(Stmt::While(cond, self.block()?));
+        }
+        if self.eat("return") {
+            if self.eat(";") {
+                return Ok(Stmt::Return(None));
+            }
+            let value = self.expr()?;
+            self.expect(";")?;
+            return Ok(Stmt::Return(Some(value)));
+        }
+
+        // `name = value;` or an expression
+        if let (Some(Token::Ident(name)), Some((Token::Sym("="), _))) =
+            (self.peek().cloned(), self.tokens.get(self.pos + 1))
+        {
+            self.pos += 2;
+            let value = self.expr()?;
+            self.expect(";")?;
+            return Ok(Stmt::Assign(name, value));
+        }
+        let e = self.expr()?;
+        self.expect(";")?;
+        Ok(Stmt::Expr(e))
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let lhs = self.sum()?;
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        for (sym, op) in ops.iter() {
+            if self.eat(sym) {
+                let rhs = self.sum()?;
+                return Ok(Expr::Binary(*op, Box::new(lhs), Box::new(rhs)));
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                Op::Add
+            } else if self.eat("-") {
+                Op::Sub
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.product()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while self.eat("*") {
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(Op::Mul, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let e = self.expr()?;
+            self.expect(")")?;
+            return Ok(e);
+        }
+
+        match self.peek().cloned() {
+            Some(Token::Num(v)) => {
+                self.pos += 1;
+                Ok(Expr::Num(v))
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                if !self.eat("(") {
+                    return Ok(Expr::Var(name));
+                }
+                let mut args = vec![];
+                while !self.eat(")") {
+                    if !args.is_empty() {
+                        self.expect(",")?;
+                    }
+                    args.push(self.expr()?);
+                }
+                Ok(Expr::Call(name, args))
+            }
+            _ => anyhow::bail!("expected an expression at line {}", self.line()),
+        }
+    }
+}
+
+pub fn parse(src: &str) -> Result<Vec<Function>> {
+    let mut parser = Parser {
+        tokens: lex(src)?,
+        pos: 0,
+    };
+    let mut functions = vec![];
+    while parser.peek().is_some() {
+        functions.push(parser.function()?);
+    }
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use super::*;
+
+    #[test]
+    fn precedence() {
+        let f = &parse("fn main() { x = 1 + 2 * -y < 3; }").unwrap()[0];
+        let rhs = Expr::Binary(
+            Op::Add,
+            Box::new(Expr::Num(1)),
+            Box::new(Expr::Binary(
+                Op::Mul,
+                Box::new(Expr::Num(2)),
+                Box::new(Expr::Neg(Box::new(Expr::Var("y".to_string())))),
+            )),
+        );
+        let value = Expr::Binary(Op::Lt, Box::new(rhs), Box::new(Expr::Num(3)));
+        assert_eq!(vec![Stmt::Assign("x".to_string(), value)], f.body);
+    }
+
+    #[test]
+    fn errors() {
+        let err = parse("fn main() {\n  let = 3;\n}").unwrap_err();
+        assert_eq!("expected a name at line 2", err.to_string());
+        assert!(parse("fn main() { $ }").is_err());
+        assert!(parse("fn main() { output(1); ").is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+use intcode::cpu;
+
+// cargo run --bin coverage -- <program> [runs]
+// every line of `runs` holds the comma separated inputs of one run, the
+// coverage of all runs is merged
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: coverage <program> [runs]");
+    }
+
+    let prog = cpu::parse_input(&args[1])?;
+    let runs = match args.get(2) {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => String::new(),
+    };
+    let mut runs: Vec<Vec<i128>> = runs
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split(',').map(|v| v.trim().parse()).collect())
+        .collect::<Result<_, _>>()?;
+    if runs.is_empty() {
+        runs.push(vec![]);
+    }
+
+    let mut coverage = cpu::coverage::Coverage::default();
+    for (n, inputs) in runs.into_iter().enumerate() {
+        let mut cpu = cpu::Cpu::new_detached(&prog);
+        cpu.track_coverage();
+        let mut out = cpu.into_outputs(inputs);
+        if let Some(Err(e)) = out.by_ref().find(|v| v.is_err()) {
+            eprintln!("run {}: {}", n, e);
+        }
+        coverage.merge(out.into_cpu().coverage().unwrap());
+    }
+
+    print!("{}", coverage.listing(&prog));
+    Ok(())
+}
@@ -1,36 +1,119 @@
 use anyhow::Result;
+use async_std::future;
 use async_std::sync::{Receiver, Sender};
+use async_std::task;
+use futures::sink::{self, Sink, SinkExt};
+use futures::stream::{self, Stream, StreamExt};
+use std::convert::TryFrom;
+use std::pin::Pin;
+use std::task::Poll;
 
+// a program that loops without any io would otherwise never give its
+// executor thread back to other tasks
+const YIELD_EVERY: usize = 1000;
+
+// the program as the other cpu loads it, header and wrapped lines included
+#[allow(dead_code)]
 pub fn parse_input(fname: &str) -> Result<Vec<isize>> {
-    let input = std::fs::read_to_string(fname)?;
-    let input = input.trim_end();
-    let result = input.split(',').map(|s| s.parse::<isize>()).collect();
-    match result {
-        Ok(v) => Ok(v),
-        Err(e) => anyhow::bail!("parse error: {}", e),
-    }
+    let (_, prog) = intcode::cpu::header::load(fname)?;
+    prog.into_iter()
+        .map(|v| match isize::try_from(v) {
+            Ok(v) => Ok(v),
+            Err(_) => anyhow::bail!("value {} doesn't fit the cpu", v),
+        })
+        .collect()
 }
 
-#[derive(Debug)]
+type Input = Pin<Box<dyn Stream<Item = Result<isize>> + Send>>;
+type Output = Pin<Box<dyn Sink<isize, Error = anyhow::Error> + Send>>;
+
 pub struct Cpu {
     pc: usize,
-    sender: Sender<isize>,
-    recver: Receiver<isize>,
+    base: isize,
+    input: Input,
+    output: Option<Output>,
+    idle_input: Option<isize>,
     pub prog: Vec<isize>,
 }
 
+// runs the program lazily, each output of the cpu is the next item of the stream.
+// the stream ends when the program halts or right after the first error.
+#[allow(dead_code)]
+pub fn outputs<I>(program: &[isize], input: I) -> impl Stream<Item = Result<isize>>
+where
+    I: Stream<Item = Result<isize>> + Send + 'static,
+{
+    let cpu = Cpu::with_io(program, Box::pin(input), None);
+
+    stream::unfold(Some(cpu), |cpu| async move {
+        let mut cpu = cpu?;
+        match cpu.next_output().await {
+            Ok(Some(val)) => Some((Ok(val), Some(cpu))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+#[allow(dead_code)]
+pub fn sender_sink(sender: Sender<isize>) -> impl Sink<isize, Error = anyhow::Error> {
+    sink::unfold(sender, |sender, val| async move {
+        sender.send(val).await;
+        Ok::<_, anyhow::Error>(sender)
+    })
+}
+
 impl Cpu {
+    #[allow(dead_code)]
     pub fn new(program: &[isize], sender: Sender<isize>, recver: Receiver<isize>) -> Cpu {
+        Cpu::from_io(program, recver, sender_sink(sender))
+    }
+
+    #[allow(dead_code)]
+    pub fn from_io<I, O, E>(program: &[isize], input: I, output: O) -> Cpu
+    where
+        I: Stream<Item = isize> + Send + 'static,
+        O: Sink<isize, Error = E> + Send + 'static,
+        E: Into<anyhow::Error> + 'static,
+    {
+        Cpu::with_io(
+            program,
+            Box::pin(input.map(Ok)),
+            Some(Box::pin(output.sink_map_err(Into::into))),
+        )
+    }
+
+    fn with_io(program: &[isize], input: Input, output: Option<Output>) -> Cpu {
         Cpu {
             pc: 0,
-            sender,
-            recver,
+            base: 0,
+            input,
+            output,
+            idle_input: None,
             prog: program.into(),
         }
     }
 
+    // instead of waiting for input, read `value` whenever the input channel is empty
+    #[allow(dead_code)]
+    pub fn with_idle_input(mut self, value: isize) -> Cpu {
+        self.idle_input = Some(value);
+        self
+    }
+
+    fn get_mem(&self, k: usize) -> isize {
+        self.prog.get(k).copied().unwrap_or(0)
+    }
+
+    fn set_mem(&mut self, k: usize, v: isize) {
+        if k >= self.prog.len() {
+            self.prog.resize(k + 1, 0);
+        }
+        self.prog[k] = v;
+    }
+
     fn parse_instruction(&self) -> Result<(isize, isize, isize, isize)> {
-        let s = format!("{}{}", "0000", self.prog[self.pc]);
+        let s = format!("{}{}", "0000", self.get_mem(self.pc));
         let inst: Vec<char> = s.chars().rev().take(5).collect();
 
         let opstr = format!("{}{}", inst[1], inst[0]);
@@ -41,39 +124,94 @@ impl Cpu {
         Ok((opcode, m1, m2, m3))
     }
 
-    fn get_param(&self, offset: usize, mode: usize) -> Result<isize> {
-        match mode {
-            0 => Ok(self.prog[self.prog[self.pc + offset] as usize]),
-            1 => Ok(self.prog[self.pc + offset]),
+    fn get_addr(&self, offset: usize, mode: usize) -> Result<usize> {
+        let addr = match mode {
+            0 => self.get_mem(self.pc + offset),
+            1 => (self.pc + offset) as isize,
+            2 => self.base + self.get_mem(self.pc + offset),
             _ => anyhow::bail!("unknown mode: {}", mode),
+        };
+        if addr < 0 {
+            anyhow::bail!("negative address '{}' at '{}'", addr, &self.pc);
+        }
+        Ok(addr as usize)
+    }
+
+    fn get_param(&self, offset: usize, mode: usize) -> Result<isize> {
+        Ok(self.get_mem(self.get_addr(offset, mode)?))
+    }
+
+    fn set_param(&mut self, offset: usize, mode: usize, v: isize) -> Result<()> {
+        let addr = match mode {
+            2 => self.get_addr(offset, 2)?,
+            _ => self.get_addr(offset, 0)?,
+        };
+        self.set_mem(addr, v);
+        Ok(())
+    }
+
+    async fn read_input(&mut self) -> Result<isize> {
+        let val = match self.idle_input {
+            Some(idle) => {
+                let input = &mut self.input;
+                match future::poll_fn(|cx| Poll::Ready(input.as_mut().poll_next(cx))).await {
+                    Poll::Ready(val) => val,
+                    Poll::Pending => {
+                        task::yield_now().await;
+                        return Ok(idle);
+                    }
+                }
+            }
+            None => self.input.next().await,
+        };
+
+        match val {
+            Some(val) => val,
+            None => anyhow::bail!("input channel closed"),
         }
     }
 
     pub async fn execute(&mut self) -> Result<()> {
+        while let Some(val) = self.next_output().await? {
+            match self.output.as_mut() {
+                Some(output) => output.send(val).await?,
+                None => anyhow::bail!("no output for '{}'", val),
+            }
+        }
+        Ok(())
+    }
+
+    // runs until the next output, None means the program halted
+    async fn next_output(&mut self) -> Result<Option<isize>> {
+        let mut steps = 0usize;
         loop {
+            steps += 1;
+            if steps == YIELD_EVERY {
+                steps = 0;
+                task::yield_now().await;
+            }
+
             match self.parse_instruction()? {
-                (1, m1, m2, _) => {
-                    let c = self.prog[self.pc + 3] as usize;
-                    self.prog[c] =
-                        self.get_param(1, m1 as usize)? + self.get_param(2, m2 as usize)?;
+                (1, m1, m2, m3) => {
+                    let val = self.get_param(1, m1 as usize)? + self.get_param(2, m2 as usize)?;
+                    self.set_param(3, m3 as usize, val)?;
                     self.pc += 4;
                 }
-                (2, m1, m2, _) => {
-                    let c = self.prog[self.pc + 3] as usize;
-                    self.prog[c] =
-                        self.get_param(1, m1 as usize)? * self.get_param(2, m2 as usize)?;
+                (2, m1, m2, m3) => {
+                    let val = self.get_param(1, m1 as usize)? * self.get_param(2, m2 as usize)?;
+                    self.set_param(3, m3 as usize, val)?;
                     self.pc += 4;
                 }
-                (3, _, _, _) => {
-                    let a = self.prog[self.pc + 1] as usize;
-                    self.prog[a] = self.recver.recv().await?;
+                (3, m1, _, _) => {
+                    let val = self.read_input().await?;
+                    self.set_param(1, m1 as usize, val)?;
                     self.pc += 2;
                 }
                 (4, m1, _, _) => {
                     let a = self.get_param(1, m1 as usize)?;
-
-                    self.sender.send(a).await;
                     self.pc += 2;
+
+                    return Ok(Some(a));
                 }
                 (5, m1, m2, _) => {
                     let a = self.get_param(1, m1 as usize)?;
@@ -93,33 +231,39 @@ impl Cpu {
                         self.pc += 3;
                     }
                 }
-                (7, m1, m2, _m3) => {
+                (7, m1, m2, m3) => {
                     let a = self.get_param(1, m1 as usize)?;
                     let b = self.get_param(2, m2 as usize)?;
-                    let c = self.prog[self.pc + 3] as usize;
                     if a < b {
-                        self.prog[c] = 1;
+                        self.set_param(3, m3 as usize, 1)?;
                     } else {
-                        self.prog[c] = 0;
+                        self.set_param(3, m3 as usize, 0)?;
                     }
                     self.pc += 4;
                 }
-                (8, m1, m2, _m3) => {
+                (8, m1, m2, m3) => {
                     let a = self.get_param(1, m1 as usize)?;
                     let b = self.get_param(2, m2 as usize)?;
-                    let c = self.prog[self.pc + 3] as usize;
                     if a == b {
-                        self.prog[c] = 1;
+                        self.set_param(3, m3 as usize, 1)?;
                     } else {
-                        self.prog[c] = 0;
+                        self.set_param(3, m3 as usize, 0)?;
                     }
                     self.pc += 4;
                 }
-                (99, _, _, _) => break,
-                _ => anyhow::bail!("unknown opcode '{}' at '{}'", self.prog[self.pc], &self.pc),
+                (9, m1, _, _) => {
+                    let a = self.get_param(1, m1 as usize)?;
+                    self.base += a;
+                    self.pc += 2;
+                }
+                (99, _, _, _) => return Ok(None),
+                _ => anyhow::bail!(
+                    "unknown opcode '{}' at '{}'",
+                    self.get_mem(self.pc),
+                    &self.pc
+                ),
             }
         }
-        Ok(())
     }
 }
 
@@ -127,6 +271,39 @@ impl Cpu {
 mod cpu_tests_await {
     use super::*;
     use async_std::sync::channel;
+    // the test tables of the other cpu, see resources/intcode-corpus.txt
+    use intcode::cpu::corpus;
+
+    #[test]
+    fn parses_wrapped_lines() {
+        let path = std::env::temp_dir().join("intcode-async-parse-test.txt");
+        std::fs::write(&path, "# name: test\n1,2,\n3,4\n").unwrap();
+        let prog = parse_input(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vec![1, 2, 3, 4], prog.unwrap());
+    }
+
+    #[test]
+    fn conformance() {
+        let cases = corpus::load("resources/intcode-corpus.txt").unwrap();
+        let failures = corpus::failures(&cases, |prog, run| {
+            let prog: Vec<isize> = prog.iter().map(|v| *v as isize).collect();
+            let inputs: Vec<isize> = run.inputs.iter().map(|v| *v as isize).collect();
+            let (tx, rx) = std::sync::mpsc::channel();
+            let output = sink::unfold(tx, |tx, v: isize| async move {
+                tx.send(v as i128)?;
+                Ok::<_, anyhow::Error>(tx)
+            });
+            let mut cpu = Cpu::from_io(&prog, stream::iter(inputs), output);
+            let error = task::block_on(cpu.execute()).err();
+            corpus::Outcome {
+                outputs: rx.try_iter().collect(),
+                memory: cpu.prog.iter().map(|v| *v as i128).collect(),
+                error: error.map(|e| e.to_string()),
+            }
+        });
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
 
     #[async_std::test]
     async fn compare_tests() {
@@ -179,4 +356,79 @@ mod cpu_tests_await {
         cpu.execute().await.unwrap();
         assert_eq!(999, rx2.recv().await.unwrap());
     }
+
+    #[async_std::test]
+    async fn relative_base() {
+        let prog: Vec<isize> = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+
+        let (_tx, rx): (Sender<isize>, Receiver<isize>) = channel(1);
+        let (tx2, rx2): (Sender<isize>, Receiver<isize>) = channel(prog.len());
+        let mut cpu = Cpu::new(&prog, tx2, rx);
+        cpu.execute().await.unwrap();
+
+        let mut output = vec![];
+        for _ in 0..prog.len() {
+            output.push(rx2.recv().await.unwrap());
+        }
+        assert_eq!(prog, output);
+    }
+
+    #[async_std::test]
+    async fn idle_input() {
+        let prog = vec![3, 9, 3, 10, 4, 9, 4, 10, 99, 0, 0];
+
+        let (tx, rx): (Sender<isize>, Receiver<isize>) = channel(1);
+        let (tx2, rx2): (Sender<isize>, Receiver<isize>) = channel(2);
+        let mut cpu = Cpu::new(&prog, tx2, rx).with_idle_input(-1);
+        tx.send(5).await;
+        cpu.execute().await.unwrap();
+        assert_eq!(5, rx2.recv().await.unwrap());
+        assert_eq!(-1, rx2.recv().await.unwrap());
+    }
+
+    #[async_std::test]
+    async fn stream_io() {
+        let prog = vec![3, 20, 3, 21, 1, 20, 21, 22, 4, 22, 99];
+
+        let (tx, rx): (Sender<isize>, Receiver<isize>) = channel(1);
+        let sink = sink::unfold(tx, |tx, val| async move {
+            tx.send(val * 10).await;
+            Ok::<_, anyhow::Error>(tx)
+        });
+        let mut cpu = Cpu::from_io(&prog, stream::iter(vec![3, 4]), sink);
+        cpu.execute().await.unwrap();
+        assert_eq!(70, rx.recv().await.unwrap());
+
+        let output: Vec<isize> = outputs(&prog, stream::iter(vec![Ok(3), Ok(4)]))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(vec![7], output);
+    }
+
+    #[async_std::test]
+    async fn piped_outputs() {
+        // doubles every input forever
+        let prog = vec![3, 20, 1002, 20, 2, 20, 4, 20, 1105, 1, 0];
+
+        let doubled = outputs(&prog, stream::iter(1..=3).map(Ok));
+        let quadrupled = outputs(&prog, doubled);
+        let output: Vec<isize> = quadrupled.take(3).map(Result::unwrap).collect().await;
+        assert_eq!(vec![4, 8, 12], output);
+    }
+
+    #[async_std::test]
+    async fn stream_errors() {
+        // the input overwrites the halt instruction
+        let prog = vec![3, 2, 99];
+
+        let mut output = Box::pin(outputs(&prog, stream::iter(vec![Ok(42)])));
+        assert!(output.next().await.unwrap().is_err());
+        assert!(output.next().await.is_none());
+
+        let mut output = Box::pin(outputs(&prog, stream::empty()));
+        assert!(output.next().await.unwrap().is_err());
+    }
 }
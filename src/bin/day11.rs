@@ -1,68 +1,15 @@
 use anyhow::Result;
-mod cpu;
+use intcode::cpu;
+mod device;
 use cpu::Cpu;
+use device::hull::HullPainter;
 use std::collections::HashMap;
-use std::thread;
 
 fn run(prog: &[i128], start_panel: u8) -> Result<HashMap<(isize, isize), u8>> {
-    let (mut cpu, tx, rx2) = Cpu::new(prog);
+    let mut robot = HullPainter::new(start_panel);
+    device::attach(&mut Cpu::new_detached(prog), &mut robot)?;
 
-    let mut curpos = (0, 0);
-    let mut direction = 0;
-    let mut panels: HashMap<(isize, isize), u8> = HashMap::new();
-
-    let th = thread::spawn(move || cpu.execute());
-
-    loop {
-        match panels.get(&curpos).unwrap_or(&start_panel) {
-            0 => tx.send(0)?,
-            _ => tx.send(1)?,
-        }
-        match rx2.recv() {
-            Ok(x) => panels.insert(curpos, x as u8),
-            Err(_) => break,
-        };
-
-        match (direction, rx2.recv()?) {
-            (0, 0) => {
-                curpos = (curpos.0 - 1, curpos.1);
-                direction = 3;
-            }
-            (0, 1) => {
-                curpos = (curpos.0 + 1, curpos.1);
-                direction = 1;
-            }
-            (1, 0) => {
-                curpos = (curpos.0, curpos.1 - 1);
-                direction = 0;
-            }
-            (1, 1) => {
-                curpos = (curpos.0, curpos.1 + 1);
-                direction = 2;
-            }
-            (2, 0) => {
-                curpos = (curpos.0 + 1, curpos.1);
-                direction = 1;
-            }
-            (2, 1) => {
-                curpos = (curpos.0 - 1, curpos.1);
-                direction = 3;
-            }
-            (3, 0) => {
-                curpos = (curpos.0, curpos.1 + 1);
-                direction = 2;
-            }
-            (3, 1) => {
-                curpos = (curpos.0, curpos.1 - 1);
-                direction = 0;
-            }
-            (dir, turn) => anyhow::bail!("Unknown direction/turn: {}/{}", dir, turn),
-        }
-    }
-
-    th.join().expect("Can't join thread")?;
-
-    Ok(panels)
+    Ok(robot.panels)
 }
 
 fn solve1(prog: &[i128]) -> Result<usize> {
@@ -1,107 +1,42 @@
 use anyhow::Result;
+use intcode::cpu;
 use rustbox::{Color, RustBox};
-use std::collections::HashMap;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
-use std::time::Duration;
-mod cpu;
-use cpu::Cpu;
+mod device;
+use cpu::scan::{Relation, Scanner};
+use cpu::{Cpu, Step};
+use device::arcade::{Arcade, BALL, EMPTY, PADDLE};
+use device::Device;
 
 const TILE_CODES: [char; 5] = [' ', '#', '▢', '▀', '●'];
 
-struct Game {
+struct Game<'a> {
     min_x: usize,
     min_y: usize,
     max_x: usize,
     max_y: usize,
-    score: usize,
-    board: HashMap<(usize, usize), char>,
     blocks_total: usize,
-    sender: Sender<i128>,
-    receiver: Receiver<i128>,
+    arcade: Arcade,
+    rb: &'a RustBox,
 }
 
-impl Game {
-    fn new(sender: Sender<i128>, receiver: Receiver<i128>) -> Result<Game> {
-        let board = HashMap::new();
-
+impl<'a> Game<'a> {
+    fn new(rb: &'a RustBox) -> Result<Game<'a>> {
         Ok(Game {
             max_x: 0,
             min_x: 0,
             max_y: 0,
             min_y: 0,
-            sender,
-            receiver,
-            board,
-            score: 0,
             blocks_total: 0,
+            arcade: Arcade::new(),
+            rb,
         })
     }
 
-    fn joystick_position(&mut self) -> Result<()> {
-        let ball_xy = self
-            .board
-            .iter()
-            .find(|((_x, _y), v)| *v == &TILE_CODES[4])
-            .unwrap_or((&(0, 0), &' '))
-            .0;
-        let paddle_xy = self
-            .board
-            .iter()
-            .find(|((_x, _y), v)| *v == &TILE_CODES[3])
-            .unwrap_or((&(0, 0), &' '))
-            .0;
-        let position = match true {
-            true if ball_xy.0 < paddle_xy.0 => -1,
-            true if ball_xy.0 > paddle_xy.0 => 1,
-            _ => 0,
-        };
-        if self.sender.send(position).is_err() {
-            anyhow::bail!("");
-        }
-
-        Ok(())
-    }
-
-    fn receive_tiles(&mut self) -> Result<()> {
-        loop {
-            if let Ok(x) = self.receiver.recv_timeout(Duration::from_millis(5)) {
-                match (x, self.receiver.recv(), self.receiver.recv()) {
-                    (-1, Ok(0), Ok(score)) => {
-                        self.score = score as usize;
-                    }
-                    (x, Ok(y), Ok(tile)) => {
-                        self.board
-                            .insert((x as usize, y as usize), TILE_CODES[tile as usize]);
-                    }
-                    _ => anyhow::bail!("received error"),
-                }
-            } else {
-                break;
-            }
-        }
-
-        Ok(())
-    }
-
-    fn run(&mut self, rb: &RustBox) -> Result<()> {
-        if self.min_y == 0 {
-            self.receive_tiles()?;
-            self.max_x = self.board.keys().max_by_key(|(x, _)| x).unwrap().0;
-            self.min_x = self.board.keys().min_by_key(|(x, _)| x).unwrap().0;
-            self.max_y = self.board.keys().max_by_key(|(_, y)| y).unwrap().1;
-            self.min_y = self.board.keys().min_by_key(|(_, y)| y).unwrap().1;
-            self.blocks_total = self.board.values().filter(|t| *t == &TILE_CODES[2]).count();
-        }
-        loop {
-            self.receive_tiles()?;
-            self.print_board(rb)?;
-
-            if self.joystick_position().is_err() {
-                break;
-            }
-        }
+    fn run(&mut self, cpu: &mut Cpu) -> Result<()> {
+        device::attach(cpu, self)?;
+        self.print_board()?;
 
+        let rb = self.rb;
         rb.print(
             0usize,
             (self.max_y + 2) as usize,
@@ -116,7 +51,7 @@ impl Game {
             rustbox::RB_NORMAL,
             Color::Blue,
             Color::Default,
-            &format!("Part 2: {}", self.score),
+            &format!("Part 2: {}", self.arcade.score),
         );
         rb.print(
             0,
@@ -138,24 +73,26 @@ impl Game {
         Ok(())
     }
 
-    fn print_board(&self, rb: &RustBox) -> Result<()> {
+    fn print_board(&self) -> Result<()> {
+        let rb = self.rb;
         rb.clear();
 
         for y in self.min_y..=self.max_y {
             for x in self.min_x..=self.max_x {
                 match self
-                    .board
-                    .get(&(x, y))
+                    .arcade
+                    .tiles
+                    .get(&(x as isize, y as isize))
                     .ok_or_else(|| anyhow::anyhow!("not found"))
                 {
-                    Ok(ch) => {
+                    Ok(tile) => {
                         rb.print_char(
                             x as usize,
                             y as usize,
                             rustbox::RB_NORMAL,
                             Color::Default,
                             Color::Default,
-                            *ch,
+                            TILE_CODES[*tile as usize],
                         );
                     }
                     Err(err) => {
@@ -165,7 +102,7 @@ impl Game {
             }
         }
 
-        let blocks = self.board.values().filter(|t| *t == &TILE_CODES[2]).count();
+        let blocks = self.arcade.blocks();
 
         rb.print(
             0usize,
@@ -175,7 +112,7 @@ impl Game {
             Color::Default,
             &format!(
                 "Score: {}  Blocks: {}/{}",
-                self.score, blocks, self.blocks_total
+                self.arcade.score, blocks, self.blocks_total
             ),
         );
 
@@ -185,20 +122,177 @@ impl Game {
     }
 }
 
+// redraws the screen whenever the joystick is read
+impl<'a> Device for Game<'a> {
+    fn input(&mut self) -> Result<Option<i128>> {
+        if self.min_y == 0 {
+            let tiles = &self.arcade.tiles;
+            self.max_x = tiles.keys().max_by_key(|(x, _)| x).unwrap().0 as usize;
+            self.min_x = tiles.keys().min_by_key(|(x, _)| x).unwrap().0 as usize;
+            self.max_y = tiles.keys().max_by_key(|(_, y)| y).unwrap().1 as usize;
+            self.min_y = tiles.keys().min_by_key(|(_, y)| y).unwrap().1 as usize;
+            self.blocks_total = self.arcade.blocks();
+        }
+        self.print_board()?;
+
+        self.arcade.input()
+    }
+
+    fn output(&mut self, value: i128) -> Result<()> {
+        self.arcade.output(value)
+    }
+}
+
+// plays without a screen and reports which joystick moves the final score
+// depends on. the joystick mostly steers jumps, those are counted apart
+fn taint(prog: &[i128], path: &str) -> Result<()> {
+    let mut cpu = Cpu::new_detached(prog);
+    cpu.track_taint();
+    let mut arcade = Arcade::new();
+    device::attach(&mut cpu, &mut arcade)?;
+
+    let report = cpu
+        .taint_report()
+        .ok_or_else(|| anyhow::anyhow!("taint not tracked"))?;
+    std::fs::write(path, report.to_json())?;
+
+    let score = report
+        .outputs
+        .chunks(3)
+        .rfind(|draw| draw.len() == 3 && draw[0].value == -1 && draw[1].value == 0)
+        .ok_or_else(|| anyhow::anyhow!("no score drawn"))?;
+    println!(
+        "score {} depends on {} of {} joystick moves, {} jumps depend on the joystick",
+        score[2].value,
+        score[2].tags.len(),
+        report.inputs.len(),
+        report.jumps.len()
+    );
+
+    Ok(())
+}
+
+// an arcade nobody plays
+struct Idle<'a>(&'a mut Arcade);
+
+impl<'a> Device for Idle<'a> {
+    fn input(&mut self) -> Result<Option<i128>> {
+        Ok(Some(0))
+    }
+
+    fn output(&mut self, value: i128) -> Result<()> {
+        self.0.output(value)
+    }
+}
+
+// the only candidate left, if any
+fn found(scanner: &Scanner) -> Option<u128> {
+    match scanner.candidates()[..] {
+        [(k, _)] => Some(k),
+        _ => None,
+    }
+}
+
+// plays without a screen, narrowing down where the ball, the paddle and the
+// score live in memory frame by frame. the paddle's screen cell is the one
+// holding a paddle tile that turns empty once the paddle moves. then plays
+// again with the joystick idle and the paddle's row pinned to paddle tiles
+fn scan(prog: &[i128]) -> Result<()> {
+    let mut cpu = Cpu::new_detached(prog);
+    let mut arcade = Arcade::new();
+    let mut ball = Scanner::new(&cpu);
+    let mut paddle = Scanner::new(&cpu);
+    let mut score = Scanner::new(&cpu);
+    // the paddle's screen cell and the paddle x it was found at
+    let mut tile: Option<(Scanner, isize)> = None;
+    let mut screen = None;
+
+    loop {
+        match cpu.step()? {
+            Step::Continue => {}
+            Step::Output(value) => arcade.output(value)?,
+            Step::NeedInput => {
+                if let (Some(b), Some(p)) = (arcade.find(BALL), arcade.find(PADDLE)) {
+                    ball.narrow(&cpu, Relation::Equals(b.0 as i128));
+                    paddle.narrow(&cpu, Relation::Equals(p.0 as i128));
+
+                    match tile.as_mut() {
+                        None => {
+                            let mut scanner = Scanner::new(&cpu);
+                            scanner.narrow(&cpu, Relation::Equals(PADDLE));
+                            tile = Some((scanner, p.0));
+                        }
+                        Some((scanner, x)) if screen.is_none() && *x != p.0 => {
+                            scanner.narrow(&cpu, Relation::Equals(EMPTY));
+                            screen = found(scanner).map(|k| (k, *x));
+                        }
+                        _ => {}
+                    }
+                }
+                score.narrow(&cpu, Relation::Equals(arcade.score));
+                let joystick = arcade.input()?.unwrap_or(0);
+                cpu.push_input(joystick);
+            }
+            Step::Halt => break,
+        }
+    }
+
+    let found = (found(&ball), found(&paddle), found(&score), screen);
+    let (ball, paddle, score, (cell, x)) = match found {
+        (Some(ball), Some(paddle), Some(score), Some(screen)) => (ball, paddle, score, screen),
+        _ => anyhow::bail!("not found: {:?}", found),
+    };
+    println!(
+        "ball x at {}, paddle x at {}, paddle tile at {}, score at {}, final score {}",
+        ball, paddle, cell, score, arcade.score
+    );
+
+    // walls are at both ends of the row
+    let width = arcade.tiles.keys().map(|xy| xy.0).max().unwrap_or(0);
+    let row = cell - x as u128;
+    let mut cpu = Cpu::new_detached(prog);
+    for k in 1..width as u128 {
+        cpu.pin(row + k, PADDLE);
+    }
+    let mut arcade = Arcade::new();
+    device::attach(&mut cpu, &mut Idle(&mut arcade))?;
+    println!(
+        "with a paddle as wide as the screen: score {}, {} blocks left",
+        arcade.score,
+        arcade.blocks()
+    );
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let mut prog = cpu::parse_input("resources/day13-input.txt")?;
     prog[0] = 2;
 
-    let (mut cpu, tx, rx) = Cpu::new(&prog);
-    let th = thread::spawn(move || cpu.execute());
+    // cargo run --bin day13 -- --taint report.json
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 3 && args[1] == "--taint" {
+        return taint(&prog, &args[2]);
+    }
+    // cargo run --bin day13 -- --scan
+    if args.len() == 2 && args[1] == "--scan" {
+        return scan(&prog);
+    }
 
-    let mut game = Game::new(tx, rx)?;
+    let mut cpu = Cpu::new_detached(&prog);
+    // cargo run --bin day13 -- --record day13.log
+    let recorder = cpu::record::from_args(&args);
+    if let Some(recorder) = &recorder {
+        recorder.start(&mut cpu);
+    }
 
     let rustbox = RustBox::init(Default::default())?;
 
-    game.run(&rustbox)?;
+    Game::new(&rustbox)?.run(&mut cpu)?;
 
-    th.join().unwrap().unwrap();
+    if let Some(recorder) = &recorder {
+        recorder.save(&cpu)?;
+    }
 
     Ok(())
 }
@@ -1,12 +1,13 @@
 use anyhow::Result;
+use intcode::cpu;
 use rustbox::{Color, RustBox};
 use std::collections::{HashMap, HashSet, VecDeque};
-use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
-use std::thread;
-mod cpu;
+mod device;
 mod util;
+use cpu::record::{self, Recorder};
 use cpu::Cpu;
+use device::droid::RepairDroid;
+use device::Device;
 
 fn print_board(
     map: &HashMap<(isize, isize), char>,
@@ -42,109 +43,54 @@ fn print_board(
     Ok(())
 }
 
-fn solve(path: &str, rb: &RustBox) -> Result<HashMap<(isize, isize), char>> {
-    let prog = cpu::parse_input(path)?;
-    let (tx, receiver): (Sender<i128>, Receiver<i128>) = mpsc::channel();
-    let (sender, rx): (Sender<i128>, Receiver<i128>) = mpsc::channel();
-
-    thread::spawn(move || {
-        let _cpu = Cpu::new_with_send_recv(&prog, sender, receiver).execute();
-    });
-
-    #[allow(unused_assignments)]
-    let (mut curpos, mut center, mut newpos) =
-        ((0isize, 0isize), (0isize, 0isize), (0isize, 0isize));
-    #[allow(unused_assignments)]
-    let mut curdir = 0;
-
-    let mut map = HashMap::new();
-    map.insert(curpos, 'S');
-
-    let mut backtrack: VecDeque<isize> = VecDeque::new();
-
-    loop {
-        let mut backtracking = false;
-        let north = (curpos.0, curpos.1 + 1);
-        let south = (curpos.0, curpos.1 - 1);
-        let west = (curpos.0 + 1, curpos.1);
-        let east = (curpos.0 - 1, curpos.1);
-
-        curdir = match (
-            map.get(&north),
-            map.get(&south),
-            map.get(&west),
-            map.get(&east),
-        ) {
-            (None, _, _, _) => 1,
-            (_, None, _, _) => 2,
-            (_, _, None, _) => 3,
-            (_, _, _, None) => 4,
-            _ => {
-                backtracking = true;
-                match backtrack.pop_front() {
-                    Some(dir) => dir,
-                    None => break,
-                }
-            }
-        };
-
-        newpos = match curdir {
-            1 => north,
-            2 => south,
-            3 => west,
-            4 => east,
-            _ => anyhow::bail!("invalid direction: {}", curdir),
-        };
-
-        tx.send(curdir as i128)?;
-
-        match rx.recv() {
-            Ok(0) => {
-                map.insert(newpos, '#');
-            }
-            Ok(x) if (x == 1) || (x == 2) => {
-                match x {
-                    1 => {
-                        map.insert(newpos, 'D');
-                        map.insert(
-                            curpos,
-                            match map.get(&curpos) {
-                                Some('D') | None => '.',
-                                Some(ch) => *ch,
-                            },
-                        );
-                    }
-                    _ => {
-                        map.insert(newpos, 'O');
-                        map.insert(curpos, '.');
-                    }
-                }
-                if !backtracking {
-                    let backdir = match curdir {
-                        1 => 2,
-                        2 => 1,
-                        3 => 4,
-                        4 => 3,
-                        _ => anyhow::bail!("invalid curdir: {} ", &curdir),
-                    };
-                    backtrack.push_front(backdir);
-                }
-                curpos = newpos;
-            }
-            Ok(x) => anyhow::bail!("invalid response {}", x),
-            Err(e) => anyhow::bail!("Error: {:?}", e),
-        }
+// keeps the droid on screen while it explores
+struct Explorer<'a> {
+    droid: RepairDroid,
+    center: (isize, isize),
+    rb: &'a RustBox,
+}
 
-        if (center.0 - curpos.0).abs() >= rb.width() as isize / 2 {
-            center.0 = curpos.0;
+impl<'a> Device for Explorer<'a> {
+    fn input(&mut self) -> Result<Option<i128>> {
+        let curpos = self.droid.pos();
+        if (self.center.0 - curpos.0).abs() >= self.rb.width() as isize / 2 {
+            self.center.0 = curpos.0;
         }
-        if (center.1 - curpos.1).abs() >= rb.height() as isize / 2 {
-            center.1 = curpos.1;
+        if (self.center.1 - curpos.1).abs() >= self.rb.height() as isize / 2 {
+            self.center.1 = curpos.1;
         }
+        print_board(&self.droid.map, &self.center, self.rb)?;
 
-        print_board(&map, &center, rb)?;
+        self.droid.input()
     }
 
+    fn output(&mut self, value: i128) -> Result<()> {
+        self.droid.output(value)
+    }
+}
+
+fn solve(
+    path: &str,
+    rb: &RustBox,
+    recorder: Option<&Recorder>,
+) -> Result<HashMap<(isize, isize), char>> {
+    let prog = cpu::parse_input(path)?;
+    let mut explorer = Explorer {
+        droid: RepairDroid::new(),
+        center: (0, 0),
+        rb,
+    };
+
+    let mut cpu = Cpu::new_detached(&prog);
+    if let Some(recorder) = recorder {
+        recorder.start(&mut cpu);
+    }
+    device::attach(&mut cpu, &mut explorer)?;
+    if let Some(recorder) = recorder {
+        recorder.save(&cpu)?;
+    }
+    let (map, center) = (explorer.droid.map, explorer.center);
+
     print_board(&map, &center, rb)?;
     rb.print(
         0,
@@ -260,8 +206,12 @@ fn solve2(map: &HashMap<(isize, isize), char>) -> Result<usize> {
 }
 
 fn main() -> Result<()> {
+    // cargo run --bin day15 -- --record day15.log
+    let args: Vec<String> = std::env::args().collect();
+    let recorder = record::from_args(&args);
+
     let rb = RustBox::init(Default::default())?;
-    let _map = solve("resources/day15-input.txt", &rb)?;
+    let _map = solve("resources/day15-input.txt", &rb, recorder.as_ref())?;
 
     Ok(())
 }
@@ -1,9 +1,11 @@
-﻿use anyhow::Result;
+use anyhow::Result;
+use cpu::record::{self, Recorder};
 use cpu::Cpu;
+use device::ascii::Ascii;
+use intcode::cpu;
 use std::collections::HashMap;
 use std::num::ParseIntError;
-use std::thread;
-mod cpu;
+mod device;
 mod util;
 
 fn parse_input(path: &str) -> Result<Vec<i128>> {
@@ -36,33 +38,17 @@ fn print_board(map: &HashMap<(isize, isize), char>) -> Result<()> {
 }
 
 fn build_map(prog: Vec<i128>) -> Result<HashMap<(isize, isize), char>> {
-    let (mut cpu, _, rx) = Cpu::new(&prog);
-    thread::spawn(move || cpu.execute());
+    let mut camera = Ascii::new(&[]);
+    device::attach(&mut Cpu::new_detached(&prog), &mut camera)?;
 
     let mut map = HashMap::new();
 
-    let (mut y, mut x) = (0, 0);
-
-    loop {
-        match rx.recv_timeout(std::time::Duration::from_millis(100)) {
-            Ok(35) => {
-                map.insert((x, y), '#');
-                x += 1
-            }
-            Ok(46) => {
-                map.insert((x, y), '.');
-                x += 1
-            }
-            Ok(94) => {
-                map.insert((x, y), '^');
-                x += 1
-            }
-            Ok(10) => {
-                y += 1;
-                x = 0
-            }
-            Ok(x) => anyhow::bail!("got invalid response: {}", x),
-            Err(_) => break,
+    for (y, line) in camera.screen.lines().enumerate() {
+        for (x, ch) in line.chars().enumerate() {
+            match ch {
+                '#' | '.' | '^' => map.insert((x as isize, y as isize), ch),
+                _ => anyhow::bail!("got invalid response: {}", ch),
+            };
         }
     }
 
@@ -211,10 +197,7 @@ fn build_path(
     path.trim_start_matches(",").to_string()
 }
 
-fn solve2(mut prog: Vec<i128>) -> Result<i128> {
-    #[allow(unused_assignments)]
-    let mut result = 0;
-
+fn solve2(mut prog: Vec<i128>, recorder: Option<&Recorder>) -> Result<i128> {
     prog[0] = 2;
 
     // manually founded these from path
@@ -223,39 +206,26 @@ fn solve2(mut prog: Vec<i128>) -> Result<i128> {
     let c = "R,12,R,4,L,6,L,8,L,8";
 
     let main = "A,B,B,C,C,A,A,B,B,C";
-    let inputs = vec![main, a, b, c, "n"];
-
-    let (mut cpu, tx, rx) = Cpu::new(&prog);
-    thread::spawn(move || cpu.execute());
-
-    for input in inputs {
-        loop {
-            match rx.recv() {
-                Ok(58) | Ok(63) => break,
-                Ok(_) => (),
-                Err(e) => anyhow::bail!("recv error: {:?}", e),
-            }
-        }
-        for byte in input.bytes() {
-            tx.send(byte as i128)?;
-        }
-        tx.send(10)?;
+    let mut robot = Ascii::new(&[main, a, b, c, "n"]);
+    let mut cpu = Cpu::new_detached(&prog);
+    if let Some(recorder) = recorder {
+        recorder.start(&mut cpu);
     }
-
-    loop {
-        match rx.recv() {
-            Ok(x) if x > 255 => {
-                result = x;
-                break;
-            }
-            _ => (),
-        }
+    device::attach(&mut cpu, &mut robot)?;
+    if let Some(recorder) = recorder {
+        recorder.save(&cpu)?;
     }
 
-    Ok(result)
+    robot
+        .answer
+        .ok_or_else(|| anyhow::anyhow!("no dust reported"))
 }
 
 fn main() -> Result<()> {
+    // cargo run --bin day17 -- --record day17.log records part 2
+    let args: Vec<String> = std::env::args().collect();
+    let recorder = record::from_args(&args);
+
     let prog = parse_input("resources/day17-input.txt")?;
     let prog2 = prog.clone();
     let map = build_map(prog)?;
@@ -267,7 +237,7 @@ fn main() -> Result<()> {
     let path = build_path(&map, origin, direction);
 
     println!("path: {}", path);
-    println!("part 2: {}", solve2(prog2)?);
+    println!("part 2: {}", solve2(prog2, recorder.as_ref())?);
 
     Ok(())
 }
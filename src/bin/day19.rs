@@ -1,34 +1,36 @@
 use anyhow::Result;
 use std::collections::{HashSet, VecDeque};
 
-mod cpu;
-use cpu::Cpu;
+use intcode::cpu;
+mod device;
+mod sweep;
+use cpu::Image;
+use device::drone;
 
 fn build_beam(prog: &[i128], size: usize) -> Result<HashSet<(usize, usize)>> {
-    let mut set = HashSet::new();
-
-    for y in 0..size {
-        for x in 0..size {
-            let (mut cpu, tx, rx) = Cpu::new(&prog);
+    let image = Image::new(prog);
+    let points = (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x, y)))
+        .collect();
 
-            tx.send(x as i128)?;
-            tx.send(y as i128)?;
-            cpu.execute()?;
+    let mut set = HashSet::new();
+    let results = sweep::all(points, |(x, y)| {
+        drone::probe(&image, *x as i128, *y as i128)
+    });
 
-            match rx.recv() {
-                Ok(1) => {
-                    print!("#");
-                    set.insert((x as usize, y as usize));
-                }
-                Ok(0) => print!(" "),
-                Ok(x) => anyhow::bail!("invalid response: {}", x),
-                Err(e) => anyhow::bail!("recv error: {}", e),
-            }
+    for ((x, y), pulled) in results {
+        if pulled? {
+            print!("#");
+            set.insert((x, y));
+        } else {
+            print!(" ");
+        }
+        if x == size - 1 {
+            println!();
         }
-        println!("");
     }
 
-    return Ok(set);
+    Ok(set)
 }
 
 fn solve1(prog: &[i128]) -> Result<usize> {
@@ -48,6 +50,7 @@ fn solve2(prog: &[i128]) -> Result<usize> {
         (1, -1),
     ];
 
+    let image = Image::new(prog);
     let mut seen = HashSet::new();
     let mut points_to_visit = VecDeque::new();
     seen.insert((5isize, 4isize));
@@ -63,25 +66,15 @@ fn solve2(prog: &[i128]) -> Result<usize> {
                 continue;
             }
 
-            let (mut cpu, tx, rx) = Cpu::new(prog);
-            tx.send(xy.0 as i128)?;
-            tx.send(xy.1 as i128)?;
-            cpu.execute()?;
-
-            match rx.recv() {
-                Ok(1) => {
-                    if seen.contains(&(xy.0 - 99 as isize, xy.1 as isize))
-                        && seen.contains(&(xy.0, xy.1 - 99))
-                        && seen.contains(&(xy.0 - 99, xy.1 - 99))
-                    {
-                        return Ok(((xy.0 - 99) * 10000 + xy.1 - 99) as usize);
-                    }
-                    points_to_visit.push_back(xy);
-                    seen.insert(xy);
+            if drone::probe(&image, xy.0 as i128, xy.1 as i128)? {
+                if seen.contains(&(xy.0 - 99 as isize, xy.1 as isize))
+                    && seen.contains(&(xy.0, xy.1 - 99))
+                    && seen.contains(&(xy.0 - 99, xy.1 - 99))
+                {
+                    return Ok(((xy.0 - 99) * 10000 + xy.1 - 99) as usize);
                 }
-                Ok(0) => {}
-                Ok(x) => anyhow::bail!("invalid response: {}", x),
-                Err(e) => anyhow::bail!("recv error: {}", e),
+                points_to_visit.push_back(xy);
+                seen.insert(xy);
             }
         }
     }
@@ -1,28 +1,38 @@
 use anyhow::Result;
 use cpu::Cpu;
-mod cpu;
+use intcode::cpu;
+use symbolic::{Constraint, End, Engine, Expr, Rel, Solver};
+mod symbolic;
 
 fn solve1(mut input: Vec<i128>, pos1: Option<i128>, pos2: Option<i128>) -> Result<i128> {
     input[1] = pos1.unwrap_or(input[1]);
     input[2] = pos2.unwrap_or(input[2]);
 
-    let (mut cpu, _, _) = Cpu::new(&input);
+    let mut cpu = Cpu::new_detached(&input);
     cpu.execute()?;
 
     Ok(cpu.get_mem(0)?)
 }
 
+// mem[0] comes out linear in noun and verb, solving for it beats trying
+// every pair
 fn solve2(input: Vec<i128>) -> Result<i128> {
-    for noun in 0..100 {
-        for verb in 0..100 {
-            let prog = input.to_vec();
-            match solve1(prog, Some(noun), Some(verb)) {
-                Ok(19690720) => return Ok(100 * noun + verb),
-                _ => (),
-            }
-        }
+    let solver = Solver::new(0, 99);
+    let paths = Engine::new(&input)
+        .symbol(1, "noun")
+        .symbol(2, "verb")
+        .explore(&solver)?;
+
+    let path = match &paths[..] {
+        [path] if path.end == End::Halt => path,
+        _ => anyhow::bail!("expecting a single halting path"),
+    };
+    let target = Constraint::new(Expr::sub(path.mem(0), Expr::Const(19690720)), Rel::Eq);
+
+    match solver.solve(&[target]) {
+        Some(model) => Ok(100 * model["noun"] + model["verb"]),
+        None => anyhow::bail!("unable to find solution!"),
     }
-    anyhow::bail!("unable to find solution!")
 }
 
 fn main() -> Result<()> {
@@ -1,12 +1,19 @@
 use anyhow::Result;
-use std::thread;
-mod cpu;
+use cpu::record::{self, Recorder};
 use cpu::Cpu;
+use intcode::cpu;
+use std::thread;
 
-fn solve(prog: &[i128], instructions: &[u8]) -> Result<i128> {
-    let (mut cpu, tx, rx) = Cpu::new(&prog);
+fn solve(prog: &[i128], instructions: &[u8], recorder: Option<&Recorder>) -> Result<i128> {
+    let (mut cpu, tx, rx) = Cpu::new(prog);
+    if let Some(recorder) = recorder {
+        recorder.start(&mut cpu);
+    }
 
-    let handle = thread::spawn(move || -> Result<()> { cpu.execute() });
+    let handle = thread::spawn(move || -> Result<Cpu> {
+        cpu.execute()?;
+        Ok(cpu)
+    });
 
     // read prompt
     while rx.recv()? != 10 {}
@@ -27,17 +34,24 @@ fn solve(prog: &[i128], instructions: &[u8]) -> Result<i128> {
         }
     };
 
-    handle.join().expect("join failed").expect("cpu failed");
+    let cpu = handle.join().expect("join failed").expect("cpu failed");
+    if let Some(recorder) = recorder {
+        recorder.save(&cpu)?;
+    }
 
     Ok(result)
 }
 
 fn main() -> Result<()> {
+    // cargo run --bin day21 -- --record day21.log records part 2
+    let args: Vec<String> = std::env::args().collect();
+    let recorder = record::from_args(&args);
+
     let prog = cpu::parse_input("resources/day21-input.txt")?;
     let part1 = b"NOT C J\nNOT A T\nOR T J\nAND D J\nWALK\n";
     let part2 = b"NOT A T\nNOT B J\nOR J T\nNOT C J\nOR J T\nNOT D J\nNOT J J\nAND T J\nAND E T\nOR H T\nAND T J\nRUN\n";
-    println!("part 1: {}", solve(&prog, part1)?);
-    println!("part 2: {}", solve(&prog, part2)?);
+    println!("part 1: {}", solve(&prog, part1, None)?);
+    println!("part 2: {}", solve(&prog, part2, recorder.as_ref())?);
 
     Ok(())
 }
@@ -1,19 +1,48 @@
 use anyhow::Result;
-use std::collections::HashMap;
-use std::sync::mpsc::Receiver;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 use std::thread;
 
-mod cpu;
-use cpu::Cpu;
+use cpu::decoder::Decoder;
+use cpu::{Cpu, Image};
+use intcode::cpu;
+
+struct Packet {
+    dest: usize,
+    x: i128,
+    y: i128,
+}
+
+fn nic() -> Decoder<Packet> {
+    Decoder::new(3).rule(&[], |v| {
+        Ok(Packet {
+            dest: v[0] as usize,
+            x: v[1],
+            y: v[2],
+        })
+    })
+}
+
+// the next value a nic sent, if any. a nic that halted can't finish its
+// packet
+fn recv(rx: &Receiver<i128>, packets: &Decoder<Packet>) -> Result<Option<i128>> {
+    match rx.try_recv() {
+        Ok(val) => Ok(Some(val)),
+        Err(TryRecvError::Empty) => Ok(None),
+        Err(TryRecvError::Disconnected) => {
+            packets.finish()?;
+            Ok(None)
+        }
+    }
+}
 
 fn solve1(prog: &[i128]) -> Result<i128> {
     let mut cpus: Vec<(Sender<i128>, Receiver<i128>)> = vec![];
     let mut handles = vec![];
+    let image = Image::new(prog);
 
     // prepare cpus with nics
     for n in 0..50 {
-        let (mut cpu, tx, rx) = Cpu::new(&prog);
+        let (mut cpu, tx, rx) = Cpu::new(&image);
         tx.send(n)?;
         cpus.push((tx, rx));
         let handle = thread::spawn(move || -> Result<()> { cpu.execute() });
@@ -21,30 +50,19 @@ fn solve1(prog: &[i128]) -> Result<i128> {
     }
 
     // send & receive
-    let mut packets = HashMap::<usize, Vec<i128>>::new();
+    let mut packets: Vec<Decoder<Packet>> = cpus.iter().map(|_| nic()).collect();
     loop {
         for (sender_index, (_tx, rx)) in cpus.iter().enumerate() {
-            match rx.try_recv() {
-                Ok(val) => {
-                    if let Some(v) = packets.get_mut(&sender_index) {
-                        v.push(val);
-                        if v.len() == 3 {
-                            let target = *v.get(0).unwrap() as usize;
-                            let x = v.get(1).unwrap();
-                            let y = v.get(2).unwrap();
-                            if target == 255usize {
-                                return Ok(*y);
-                            }
-                            let cpu = &cpus[target];
-                            cpu.0.send(*x)?;
-                            cpu.0.send(*y)?;
-                            packets.remove(&sender_index);
-                        }
-                    } else {
-                        packets.insert(sender_index, vec![val]);
+            if let Some(val) = recv(rx, &packets[sender_index])? {
+                if let Some(event) = packets[sender_index].push(val)? {
+                    let packet = event.record;
+                    if packet.dest == 255usize {
+                        return Ok(packet.y);
                     }
+                    let cpu = &cpus[packet.dest];
+                    cpu.0.send(packet.x)?;
+                    cpu.0.send(packet.y)?;
                 }
-                _ => {}
             }
         }
         for (_cpu, (tx, _)) in cpus.iter().enumerate() {
@@ -56,17 +74,18 @@ fn solve1(prog: &[i128]) -> Result<i128> {
 fn solve2(prog: &[i128]) -> Result<i128> {
     let mut cpus: Vec<(Sender<i128>, Receiver<i128>)> = vec![];
     let mut handles = vec![];
+    let image = Image::new(prog);
 
     // prepare cpus with nics
     for n in 0..50 {
-        let (mut cpu, tx, rx) = Cpu::new(&prog);
+        let (mut cpu, tx, rx) = Cpu::new(&image);
         tx.send(n)?;
         cpus.push((tx, rx));
         let handle = thread::spawn(move || -> Result<()> { cpu.execute() });
         handles.push(handle);
     }
 
-    let mut packets = HashMap::<usize, Vec<i128>>::new();
+    let mut packets: Vec<Decoder<Packet>> = cpus.iter().map(|_| nic()).collect();
     let mut nat_x = 0;
     let mut nat_y = 0;
     let mut last_y = None;
@@ -76,32 +95,21 @@ fn solve2(prog: &[i128]) -> Result<i128> {
     loop {
         let mut idle = true;
         for (sender_index, (_tx, rx)) in cpus.iter().enumerate() {
-            match rx.try_recv() {
-                Ok(val) => {
-                    idle = false;
-                    if let Some(v) = packets.get_mut(&sender_index) {
-                        v.push(val);
-                        if v.len() == 3 {
-                            let target = *v.get(0).unwrap() as usize;
-                            let x = v.get(1).unwrap();
-                            let y = v.get(2).unwrap();
-                            if target == 255usize {
-                                nat_x = *x;
-                                nat_y = *y;
-                                send_nat = true;
-                                idle = true;
-                            } else {
-                                let cpu = &cpus[target];
-                                cpu.0.send(*x)?;
-                                cpu.0.send(*y)?;
-                            }
-                            packets.remove(&sender_index);
-                        }
+            if let Some(val) = recv(rx, &packets[sender_index])? {
+                idle = false;
+                if let Some(event) = packets[sender_index].push(val)? {
+                    let packet = event.record;
+                    if packet.dest == 255usize {
+                        nat_x = packet.x;
+                        nat_y = packet.y;
+                        send_nat = true;
+                        idle = true;
                     } else {
-                        packets.insert(sender_index, vec![val]);
+                        let cpu = &cpus[packet.dest];
+                        cpu.0.send(packet.x)?;
+                        cpu.0.send(packet.y)?;
                     }
                 }
-                _ => {}
             }
         }
 
@@ -109,7 +117,7 @@ fn solve2(prog: &[i128]) -> Result<i128> {
             tx.send(-1).unwrap();
         }
 
-        if idle && packets.is_empty() && send_nat {
+        if idle && packets.iter().all(|p| p.pending().is_empty()) && send_nat {
             cpus[0].0.send(nat_x)?;
             cpus[0].0.send(nat_y)?;
             send_nat = false;
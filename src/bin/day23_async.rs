@@ -0,0 +1,51 @@
+use anyhow::Result;
+use async_std::task;
+use std::time::Duration;
+mod cpu_async;
+mod network;
+use network::Network;
+
+async fn solve1(prog: &[isize]) -> Result<isize> {
+    let net = Network::boot(prog, 50, 16).await?;
+    let packet = net.nat().recv().await?;
+    net.shutdown().await?;
+
+    Ok(packet.y)
+}
+
+// runs `nodes` nics for a while and reports how many packets got through
+async fn stress(prog: &[isize], nodes: usize, secs: u64) -> Result<()> {
+    let net = Network::boot(prog, nodes, 16).await?;
+
+    let nat = net.nat().clone();
+    task::spawn(async move { while nat.recv().await.is_ok() {} });
+
+    task::sleep(Duration::from_secs(secs)).await;
+    let metrics = net.shutdown().await?;
+
+    println!(
+        "nodes: {} routed: {} nat: {} dropped: {} crashed: {} elapsed: {:.2}s throughput: {:.0} packets/s",
+        metrics.nodes,
+        metrics.routed,
+        metrics.nat,
+        metrics.dropped,
+        metrics.crashed,
+        metrics.elapsed.as_secs_f64(),
+        metrics.throughput()
+    );
+
+    Ok(())
+}
+
+#[async_std::main]
+async fn main() -> Result<()> {
+    let prog = cpu_async::parse_input("resources/day23-input.txt")?;
+    println!("part 1: {}", solve1(&prog).await?);
+
+    // cargo run --bin day23_async -- 10000
+    if let Some(nodes) = std::env::args().nth(1) {
+        stress(&prog, nodes.parse()?, 5).await?;
+    }
+
+    Ok(())
+}
@@ -1,27 +1,16 @@
 use anyhow::Result;
-use std::thread;
 
-mod cpu;
-use cpu::Cpu;
+use intcode::cpu;
 
 fn solve(input: i128) -> Result<i128> {
     let prog = cpu::parse_input("resources/day5-input.txt")?;
 
-    let (mut cpu, tx, rx) = Cpu::new(&prog);
-    tx.send(input)?;
-
-    let t = thread::spawn(move || cpu.execute());
-
-    loop {
-        let response = rx.recv()?;
-        if response != 0 {
-            match t.join().unwrap() {
-                Ok(_) => {
-                    return Ok(response);
-                }
-                Err(_) => anyhow::bail!("cpu execution failed with input: {}", input),
-            }
-        }
+    let outputs = cpu::run_with_inputs(&prog, &[input])?;
+
+    // every test before the diagnostic code reports 0 on success
+    match outputs.split_last() {
+        Some((code, tests)) if tests.iter().all(|val| *val == 0) => Ok(*code),
+        _ => anyhow::bail!("diagnostics failed with input {}: {:?}", input, outputs),
     }
 }
 
@@ -34,6 +23,7 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod day5_tests {
     use super::*;
+    use cpu::Cpu;
     use std::sync::mpsc::{Receiver, Sender};
 
     fn new_cpu(fname: &str) -> (Cpu, Sender<i128>, Receiver<i128>) {
@@ -1,106 +1,89 @@
 use anyhow::Result;
+use intcode::cpu;
 use itertools::Itertools;
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::thread;
-use std::time::Duration;
-mod cpu;
-use cpu::Cpu;
-
-fn create_amp_circuit(
-    prog: &[i128],
-    init_seq: &[i128],
-) -> Result<(Vec<Cpu>, Sender<i128>, Receiver<i128>)> {
-    let mut senders: Vec<Sender<i128>> = vec![];
-    let mut recvers: Vec<Receiver<i128>> = vec![];
-    let mut cpus: Vec<Cpu> = vec![];
-
-    for _ in 0..=init_seq.len() {
-        let (tx, rx): (Sender<i128>, Receiver<i128>) = mpsc::channel();
-        senders.push(tx);
-        recvers.push(rx);
-    }
-
-    for (sender, init) in senders.iter().zip(init_seq.iter()) {
-        sender.send(*init)?;
-    }
-
-    let sender = senders.remove(0);
-
-    for _ in 0..init_seq.len() {
-        let cpu = Cpu::new_with_send_recv(prog, senders.remove(0), recvers.remove(0));
-        cpus.push(cpu);
-    }
-
-    let recver = recvers.remove(0);
-
-    anyhow::ensure!(senders.is_empty(), "expecting 0 senders");
-    anyhow::ensure!(recvers.is_empty(), "expecting 0 receivers");
-
-    Ok((cpus, sender, recver))
-}
-
-fn solve1(prog: &[i128]) -> Result<u128> {
-    let mut result = 0u128;
-    let perms = (0..=4).permutations(5);
-
-    for seq in perms {
-        let (cpus, sender, recver) = create_amp_circuit(prog, &seq)?;
-
-        sender.send(0)?;
-
-        let mut handles = vec![];
-
-        for mut cpu in cpus {
-            let th = thread::spawn(move || cpu.execute());
-            handles.push(th);
-        }
-        result = std::cmp::max(recver.recv()? as u128, result);
-
-        for th in handles {
-            th.join().unwrap()?;
-        }
+mod sweep;
+use cpu::{Cpu, Step};
+
+// Runs each amplifier to the end on its phase and the signal of the one
+// before it. No threads, so a sweep can run many chains on its pool.
+fn run_chain(prog: &[i128], seq: &[i128]) -> Result<i128> {
+    let mut signal = 0;
+    for phase in seq {
+        signal = match cpu::run_with_inputs(prog, &[*phase, signal])?.first() {
+            Some(signal) => *signal,
+            None => anyhow::bail!("the amplifier with phase {} sent no signal", phase),
+        };
     }
-
-    Ok(result)
+    Ok(signal)
 }
 
-fn solve2(prog: &[i128]) -> Result<u128> {
-    let mut result = 0u128;
-    let perms = (5..=9).permutations(5);
-
-    for seq in perms {
-        let (cpus, sender, recver) = create_amp_circuit(prog, &seq)?;
-
-        sender.send(0)?;
-
-        let mut handles = vec![];
-
-        for mut cpu in cpus {
-            let th = thread::spawn(move || cpu.execute());
-            handles.push(th);
-        }
-
-        let d = Duration::from_millis(50);
-        loop {
-            match recver.recv_timeout(d) {
-                Ok(val) => {
-                    result = std::cmp::max(val as u128, result);
-                    if let Err(_) = sender.send(val) {
+// Steps the amplifiers in turn, each until it wants an input or halts, and
+// feeds the signals of the last one back into the first. No threads, so a
+// sweep can run many circuits on its pool.
+fn run_feedback(prog: &[i128], seq: &[i128]) -> Result<i128> {
+    let mut amps: Vec<Cpu> = seq
+        .iter()
+        .map(|phase| {
+            let mut amp = Cpu::new_detached(prog);
+            amp.push_input(*phase);
+            amp
+        })
+        .collect();
+
+    let mut signals = vec![0];
+    let mut result = None;
+    loop {
+        let mut halted = 0;
+        for amp in amps.iter_mut() {
+            for signal in signals.drain(..) {
+                amp.push_input(signal);
+            }
+            loop {
+                match amp.step()? {
+                    Step::Continue => {}
+                    Step::Output(v) => signals.push(v),
+                    Step::NeedInput => break,
+                    Step::Halt => {
+                        halted += 1;
                         break;
                     }
                 }
-                Err(_) => {
-                    break;
-                }
             }
         }
 
-        for th in handles {
-            th.join().unwrap()?;
+        if let Some(signal) = signals.last() {
+            result = Some(*signal);
+        }
+        if halted == amps.len() {
+            break;
         }
+        if signals.is_empty() {
+            anyhow::bail!("the amplifiers wait on each other");
+        }
+    }
+
+    match result {
+        Some(result) => Ok(result),
+        None => anyhow::bail!("no signal reached the thrusters"),
     }
+}
 
-    Ok(result)
+// every permutation runs its own circuit, the sweep spreads them over the
+// rayon pool
+fn solve1(prog: &[i128]) -> Result<i128> {
+    let perms = (0..=4).permutations(5).collect();
+    match sweep::best(perms, |seq| run_chain(prog, seq)) {
+        Some((_, result)) => Ok(result),
+        None => anyhow::bail!("no phase sequence succeeded"),
+    }
+}
+
+fn solve2(prog: &[i128]) -> Result<i128> {
+    let perms = (5..=9).permutations(5).collect();
+    match sweep::best(perms, |seq| run_feedback(prog, seq)) {
+        Some((_, result)) => Ok(result),
+        None => anyhow::bail!("no phase sequence succeeded"),
+    }
 }
 
 fn main() -> Result<()> {
@@ -116,7 +99,7 @@ mod day7_tests {
 
     #[test]
     fn test_programs() {
-        let progs = vec![
+        let progs = [
             vec![
                 3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
             ],
@@ -130,32 +113,29 @@ mod day7_tests {
             ],
         ];
 
-        let init_seqs = vec![
+        let init_seqs = [
             vec![4, 3, 2, 1, 0],
             vec![0, 1, 2, 3, 4],
             vec![1, 0, 4, 3, 2],
         ];
 
-        let results = vec![43210, 54321, 65210];
+        let results = [43210, 54321, 65210];
 
         for n in 0..progs.len() {
-            let (cpus, sender, recver) = create_amp_circuit(&progs[n], &init_seqs[n]).unwrap();
-
-            sender.send(0).unwrap();
-
-            let mut handles = vec![];
+            assert_eq!(results[n], run_chain(&progs[n], &init_seqs[n]).unwrap());
+        }
+    }
 
-            for mut cpu in cpus {
-                let th = thread::spawn(move || {
-                    cpu.execute().unwrap();
-                });
-                handles.push(th);
-            }
-            assert_eq!(results[n], recver.recv().unwrap());
+    #[test]
+    fn feedback() {
+        let prog = vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ];
+        assert_eq!(139629729, run_feedback(&prog, &[9, 8, 7, 6, 5]).unwrap());
 
-            for th in handles {
-                th.join().unwrap();
-            }
-        }
+        // the second amplifier waits for a signal the first never sends
+        let prog = vec![3, 9, 3, 9, 99];
+        assert!(run_feedback(&prog, &[5, 6]).is_err());
     }
 }
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use async_std::sync::{channel, Receiver, Sender};
 use async_std::task;
+use futures::stream::{self, StreamExt};
 use itertools::Itertools;
 mod cpu_async;
 use cpu_async::Cpu;
@@ -38,29 +39,26 @@ fn create_amp_circuit(
     Ok((cpus, sender, recver))
 }
 
+// part 1 amplifiers form a plain pipeline, so their output streams are chained
+async fn run_amp_chain(prog: &[isize], init_seq: &[isize]) -> Result<isize> {
+    let mut signal = stream::iter(vec![Ok(0)]).boxed();
+
+    for init in init_seq {
+        signal = cpu_async::outputs(prog, stream::iter(vec![Ok(*init)]).chain(signal)).boxed();
+    }
+
+    match signal.next().await {
+        Some(val) => val,
+        None => anyhow::bail!("no output signal"),
+    }
+}
+
 async fn solve1(prog: &[isize]) -> Result<usize> {
     let mut result = 0usize;
     let perms = (0..=4).permutations(5);
 
     for seq in perms {
-        let (cpus, sender, recver) = create_amp_circuit(prog, &seq)?;
-
-        task::spawn(async move {
-            sender.send(0).await;
-        });
-
-        let mut jhs = vec![];
-
-        for mut cpu in cpus {
-            let jh = task::spawn(async move { cpu.execute().await });
-            jhs.push(jh);
-        }
-
-        for jh in jhs {
-            jh.await?;
-        }
-
-        result = std::cmp::max(recver.recv().await? as usize, result);
+        result = std::cmp::max(run_amp_chain(prog, &seq).await? as usize, result);
     }
 
     Ok(result)
@@ -110,3 +108,16 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod day7_async_tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_amp_chain() {
+        let prog = vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ];
+        assert_eq!(43210, run_amp_chain(&prog, &[4, 3, 2, 1, 0]).await.unwrap());
+    }
+}
@@ -1,14 +1,11 @@
 use anyhow::Result;
-mod cpu;
-use cpu::Cpu;
+use intcode::cpu;
 
 fn solve(prog: &[i128], input: i128) -> Result<i128> {
-    let (mut cpu, tx1, rx2) = Cpu::new(&prog);
-
-    tx1.send(input)?;
-    cpu.execute()?;
-
-    Ok(rx2.recv()?)
+    match cpu::run_with_inputs(prog, &[input])?.first() {
+        Some(val) => Ok(*val),
+        None => anyhow::bail!("no output"),
+    }
 }
 
 fn main() -> Result<()> {
@@ -0,0 +1,230 @@
+use anyhow::Result;
+use cpu::debug::{Session, State};
+use cpu::record::Event;
+use intcode::cpu;
+use rustbox::{Color, Key, RustBox};
+use std::time::Duration;
+
+// the disassembly takes the left of the screen, the rest is split between
+// registers, the i/o log and memory
+const CODE_WIDTH: usize = 44;
+const CELL_WIDTH: usize = 10;
+
+const HELP: &str = "s step  c run  b breakpoint  i input  g memory  PgUp/PgDn scroll  q quit";
+
+struct Debugger<'a> {
+    session: Session,
+    rb: &'a RustBox,
+    // first address of the memory view
+    mem_top: u128,
+}
+
+impl<'a> Debugger<'a> {
+    fn print(&self, x: usize, y: usize, fg: Color, text: &str) {
+        self.rb
+            .print(x, y, rustbox::RB_NORMAL, fg, Color::Default, text);
+    }
+
+    fn cells_per_row(&self) -> usize {
+        ((self.rb.width().saturating_sub(CODE_WIDTH + 8)) / CELL_WIDTH).max(1)
+    }
+
+    fn memory_rows(&self) -> usize {
+        (self.rb.height() / 2).saturating_sub(2).max(1)
+    }
+
+    fn draw(&mut self) {
+        let rb = self.rb;
+        rb.clear();
+        let (width, height) = (rb.width(), rb.height());
+
+        let pc = self.session.cpu.pc();
+        for (y, inst) in self
+            .session
+            .disassembly(height.saturating_sub(1))
+            .iter()
+            .enumerate()
+        {
+            let at = inst.pc as u128;
+            let mark = match (at == pc, self.session.breakpoints.contains(&at)) {
+                (true, true) => "*>",
+                (true, false) => " >",
+                (false, true) => "* ",
+                _ => "  ",
+            };
+            let fg = if at == pc {
+                Color::Yellow
+            } else {
+                Color::Default
+            };
+            self.print(0, y, fg, &format!("{}{:>6}  {}", mark, inst.pc, inst.text));
+        }
+
+        let x = CODE_WIDTH;
+        let state = match &self.session.state {
+            State::Ready => "ready".to_string(),
+            State::Waiting => "waiting for input".to_string(),
+            State::Halted => "halted".to_string(),
+            State::Fault(e) => e.clone(),
+        };
+        let cpu = &self.session.cpu;
+        self.print(x, 0, Color::Cyan, &format!("pc    {}", cpu.pc()));
+        self.print(x, 1, Color::Cyan, &format!("base  {}", cpu.base()));
+        self.print(x, 2, Color::Cyan, &format!("count {}", cpu.count()));
+        self.print(x, 3, Color::Red, &state);
+
+        // the newest events that fit above the memory view, which is left out
+        // when the screen is too short for both
+        let log_top = 5;
+        let mem_y = height.saturating_sub(self.memory_rows() + 2);
+        let shown = mem_y.saturating_sub(log_top + 1);
+        let log = &self.session.log;
+        for (y, event) in log[log.len().saturating_sub(shown)..].iter().enumerate() {
+            let (fg, text) = match event {
+                Event::Input(v) => (Color::Green, format!("in  {}", v)),
+                Event::Output(v) => (Color::Blue, format!("out {}", v)),
+            };
+            self.print(x, log_top + y, fg, &text);
+        }
+
+        let per_row = self.cells_per_row();
+        let mem_rows = if mem_y > log_top {
+            self.memory_rows()
+        } else {
+            0
+        };
+        let rows = self.session.memory(self.mem_top, mem_rows, per_row);
+        for (y, row) in rows.iter().enumerate() {
+            let addr = self.mem_top + (y * per_row) as u128;
+            self.print(x, mem_y + y, Color::Default, &format!("{:>6}:", addr));
+            for (col, (text, written)) in row.iter().enumerate() {
+                let fg = if *written {
+                    Color::Magenta
+                } else {
+                    Color::Default
+                };
+                let cell = format!("{:>w$}", text, w = CELL_WIDTH);
+                self.print(x + 7 + col * CELL_WIDTH, mem_y + y, fg, &cell);
+            }
+        }
+
+        if let Some(y) = height.checked_sub(1) {
+            self.print(0, y, Color::Default, &HELP[..HELP.len().min(width)]);
+        }
+        rb.present();
+    }
+
+    // a line typed on the bottom row, `None` when escaped
+    fn prompt(&mut self, label: &str) -> Result<Option<String>> {
+        let mut line = String::new();
+        loop {
+            self.draw();
+            let y = self.rb.height().saturating_sub(1);
+            self.print(0, y, Color::Default, &" ".repeat(self.rb.width()));
+            self.print(0, y, Color::Yellow, &format!("{}{}", label, line));
+            self.rb.present();
+
+            match self.rb.poll_event(false) {
+                Ok(rustbox::Event::KeyEvent(key)) => match key {
+                    Key::Enter => return Ok(Some(line)),
+                    Key::Esc => return Ok(None),
+                    Key::Backspace => {
+                        line.pop();
+                    }
+                    Key::Char(c) => line.push(c),
+                    _ => {}
+                },
+                Ok(_) => {}
+                Err(e) => anyhow::bail!(e),
+            }
+        }
+    }
+
+    // runs until a breakpoint, a stop or any key
+    fn run(&mut self) -> Result<()> {
+        while !self.session.run(10000) {
+            self.draw();
+            match self.rb.peek_event(Duration::from_millis(0), false) {
+                Ok(rustbox::Event::KeyEvent(_)) => break,
+                Ok(_) => {}
+                Err(e) => anyhow::bail!(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn main_loop(&mut self) -> Result<()> {
+        loop {
+            self.draw();
+            let key = match self.rb.poll_event(false) {
+                Ok(rustbox::Event::KeyEvent(key)) => key,
+                Ok(_) => continue,
+                Err(e) => anyhow::bail!(e),
+            };
+            let row = self.cells_per_row() as u128;
+            let page = self.memory_rows() as u128 * row;
+            match key {
+                Key::Char('q') | Key::Esc => break,
+                Key::Char('s') => self.session.step(),
+                Key::Char('c') => self.run()?,
+                Key::Char('b') => {
+                    if let Some(line) = self.prompt("breakpoint at (pc): ")? {
+                        let addr = match line.trim() {
+                            "" => Ok(self.session.cpu.pc()),
+                            addr => addr.parse(),
+                        };
+                        if let Ok(addr) = addr {
+                            self.session.toggle_breakpoint(addr);
+                        }
+                    }
+                }
+                Key::Char('i') => {
+                    if let Some(line) = self.prompt("input: ")? {
+                        // anything that isn't a number is skipped
+                        for v in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                            if let Ok(v) = v.parse() {
+                                self.session.input(v);
+                            }
+                        }
+                    }
+                }
+                Key::Char('g') => {
+                    let line = self.prompt("memory at: ")?.unwrap_or_default();
+                    if let Ok(addr) = line.trim().parse() {
+                        self.mem_top = addr;
+                    }
+                }
+                Key::PageDown => self.mem_top += page,
+                Key::PageUp => self.mem_top = self.mem_top.saturating_sub(page),
+                Key::Down => self.mem_top += row,
+                Key::Up => self.mem_top = self.mem_top.saturating_sub(row),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// cargo run --bin debug -- resources/day9-input.txt 1
+// steps through any program with its disassembly, registers, i/o and
+// memory on screen. the numbers after the program are queued as inputs
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: debug <program> [inputs ...]");
+    }
+
+    let prog = cpu::parse_input(&args[1])?;
+    let mut session = Session::new(&prog);
+    for v in args[2..].iter() {
+        session.input(v.parse()?);
+    }
+
+    let rb = RustBox::init(Default::default())?;
+    let mut debugger = Debugger {
+        session,
+        rb: &rb,
+        mem_top: 0,
+    };
+    debugger.main_loop()
+}
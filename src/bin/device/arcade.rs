@@ -0,0 +1,128 @@
+use super::Device;
+use crate::cpu::decoder::Decoder;
+use anyhow::Result;
+use std::collections::HashMap;
+
+pub const EMPTY: i128 = 0;
+#[allow(dead_code)]
+pub const WALL: i128 = 1;
+pub const BLOCK: i128 = 2;
+pub const PADDLE: i128 = 3;
+pub const BALL: i128 = 4;
+
+// day 13: draws (x, y, tile) triples, (-1, 0, score) updates the score
+// display. the joystick follows the ball with the paddle
+pub struct Arcade {
+    pub tiles: HashMap<(isize, isize), i128>,
+    pub score: i128,
+    screen: Decoder<Draw>,
+}
+
+enum Draw {
+    Score(i128),
+    Tile((isize, isize), i128),
+}
+
+impl Arcade {
+    #[allow(dead_code)]
+    pub fn new() -> Arcade {
+        Arcade {
+            tiles: HashMap::new(),
+            score: 0,
+            screen: Decoder::new(3)
+                .rule(&[Some(-1), Some(0)], |v| Ok(Draw::Score(v[2])))
+                .rule(&[], |v| match v[2] {
+                    EMPTY..=BALL => Ok(Draw::Tile((v[0] as isize, v[1] as isize), v[2])),
+                    _ => anyhow::bail!("unknown tile: {}", v[2]),
+                }),
+        }
+    }
+
+    pub fn find(&self, tile: i128) -> Option<(isize, isize)> {
+        self.tiles
+            .iter()
+            .find(|(_, t)| **t == tile)
+            .map(|(xy, _)| *xy)
+    }
+
+    #[allow(dead_code)]
+    pub fn blocks(&self) -> usize {
+        self.tiles.values().filter(|t| **t == BLOCK).count()
+    }
+}
+
+impl Device for Arcade {
+    fn input(&mut self) -> Result<Option<i128>> {
+        let position = match (self.find(BALL), self.find(PADDLE)) {
+            (Some(ball), Some(paddle)) => (ball.0 - paddle.0).signum() as i128,
+            _ => 0,
+        };
+        Ok(Some(position))
+    }
+
+    fn output(&mut self, value: i128) -> Result<()> {
+        match self.screen.push(value)?.map(|event| event.record) {
+            Some(Draw::Score(score)) => self.score = score,
+            Some(Draw::Tile(xy, tile)) => {
+                self.tiles.insert(xy, tile);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn halt(&mut self) -> Result<()> {
+        self.screen.finish()
+    }
+}
+
+#[cfg(test)]
+mod arcade_tests {
+    use super::*;
+    use crate::device::{attach, Event::*, Script};
+
+    #[test]
+    fn draws_and_steers() {
+        let mut arcade = Arcade::new();
+        let mut script = Script::new(vec![
+            Write(1),
+            Write(2),
+            Write(PADDLE),
+            Write(6),
+            Write(5),
+            Write(BALL),
+            Write(0),
+            Write(0),
+            Write(BLOCK),
+            Read,
+            Write(6),
+            Write(5),
+            Write(EMPTY),
+            Write(0),
+            Write(5),
+            Write(BALL),
+            Write(-1),
+            Write(0),
+            Write(12),
+            Read,
+            Read,
+        ]);
+
+        attach(&mut script, &mut arcade).unwrap();
+        assert_eq!(vec![1, -1, -1], script.inputs);
+        assert_eq!(12, arcade.score);
+        assert_eq!(1, arcade.blocks());
+    }
+
+    #[test]
+    fn unknown_tile() {
+        let mut arcade = Arcade::new();
+        let mut script = Script::new(vec![Write(1), Write(1), Write(9)]);
+        assert!(attach(&mut script, &mut arcade).is_err());
+
+        // a halt in the middle of a triple
+        let mut arcade = Arcade::new();
+        let mut script = Script::new(vec![Write(1), Write(1)]);
+        assert!(attach(&mut script, &mut arcade).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use super::Device;
+use anyhow::Result;
+use std::collections::VecDeque;
+
+// day 17: an ASCII terminal. lines are typed in as the program asks for
+// them, anything past the ASCII range is the program's answer
+pub struct Ascii {
+    typed: VecDeque<i128>,
+    pub screen: String,
+    pub answer: Option<i128>,
+}
+
+impl Ascii {
+    #[allow(dead_code)]
+    pub fn new(lines: &[&str]) -> Ascii {
+        let typed = lines
+            .iter()
+            .flat_map(|line| line.bytes().chain(Some(b'\n')))
+            .map(|byte| byte as i128)
+            .collect();
+
+        Ascii {
+            typed,
+            screen: String::new(),
+            answer: None,
+        }
+    }
+}
+
+impl Device for Ascii {
+    fn input(&mut self) -> Result<Option<i128>> {
+        Ok(self.typed.pop_front())
+    }
+
+    fn output(&mut self, value: i128) -> Result<()> {
+        match value {
+            0..=255 => self.screen.push(value as u8 as char),
+            _ => self.answer = Some(value),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ascii_tests {
+    use super::*;
+    use crate::device::{attach, Event::*, Script};
+
+    #[test]
+    fn types_lines() {
+        let mut term = Ascii::new(&["A,B", "n"]);
+        let mut script = Script::new(vec![
+            Write(62),
+            Read,
+            Read,
+            Read,
+            Read,
+            Read,
+            Read,
+            Write(35),
+            Write(10),
+            Write(1234),
+        ]);
+
+        attach(&mut script, &mut term).unwrap();
+        assert_eq!(vec![65, 44, 66, 10, 110, 10], script.inputs);
+        assert_eq!(">#\n", term.screen);
+        assert_eq!(Some(1234), term.answer);
+    }
+}
@@ -0,0 +1,172 @@
+use super::Device;
+use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
+
+// day 15: walks the droid depth first until every reachable tile is known.
+// the map marks the (S)tart, the (D)roid, the (O)xygen system and walls
+pub struct RepairDroid {
+    pub map: HashMap<(isize, isize), char>,
+    pos: (isize, isize),
+    backtrack: VecDeque<i128>,
+    moving: Option<(i128, (isize, isize), bool)>,
+}
+
+impl RepairDroid {
+    #[allow(dead_code)]
+    pub fn new() -> RepairDroid {
+        let mut map = HashMap::new();
+        map.insert((0, 0), 'S');
+
+        RepairDroid {
+            map,
+            pos: (0, 0),
+            backtrack: VecDeque::new(),
+            moving: None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn pos(&self) -> (isize, isize) {
+        self.pos
+    }
+
+    fn neighbour(&self, direction: i128) -> (isize, isize) {
+        let (x, y) = self.pos;
+        match direction {
+            1 => (x, y + 1),
+            2 => (x, y - 1),
+            3 => (x + 1, y),
+            _ => (x - 1, y),
+        }
+    }
+}
+
+impl Device for RepairDroid {
+    fn input(&mut self) -> Result<Option<i128>> {
+        let unknown = (1..=4).find(|dir| !self.map.contains_key(&self.neighbour(*dir)));
+
+        let (direction, backtracking) = match unknown {
+            Some(dir) => (dir, false),
+            None => match self.backtrack.pop_front() {
+                Some(dir) => (dir, true),
+                None => return Ok(None),
+            },
+        };
+
+        self.moving = Some((direction, self.neighbour(direction), backtracking));
+        Ok(Some(direction))
+    }
+
+    fn output(&mut self, value: i128) -> Result<()> {
+        let (direction, newpos, backtracking) = self
+            .moving
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("droid reported without moving"))?;
+
+        match value {
+            0 => {
+                self.map.insert(newpos, '#');
+                return Ok(());
+            }
+            1 => {
+                self.map.insert(newpos, 'D');
+                let here = match self.map.get(&self.pos) {
+                    Some('D') | None => '.',
+                    Some(ch) => *ch,
+                };
+                self.map.insert(self.pos, here);
+            }
+            2 => {
+                self.map.insert(newpos, 'O');
+                self.map.insert(self.pos, '.');
+            }
+            _ => anyhow::bail!("invalid response {}", value),
+        }
+
+        if !backtracking {
+            let back = match direction {
+                1 => 2,
+                2 => 1,
+                3 => 4,
+                _ => 3,
+            };
+            self.backtrack.push_front(back);
+        }
+        self.pos = newpos;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod droid_tests {
+    use super::*;
+    use crate::cpu::Step;
+    use crate::device::{attach, Bus};
+
+    // a fake cpu over a tiny maze instead of a program
+    struct Maze {
+        walls: Vec<&'static str>,
+        pos: (isize, isize),
+        moves: usize,
+        reply: Option<i128>,
+        pushed: Option<i128>,
+    }
+
+    impl Bus for Maze {
+        fn step(&mut self) -> Result<Step> {
+            if let Some(reply) = self.reply.take() {
+                return Ok(Step::Output(reply));
+            }
+            let direction = match self.pushed.take() {
+                Some(direction) => direction,
+                None => return Ok(Step::NeedInput),
+            };
+
+            self.moves += 1;
+            let (x, y) = self.pos;
+            let next = match direction {
+                1 => (x, y + 1),
+                2 => (x, y - 1),
+                3 => (x + 1, y),
+                _ => (x - 1, y),
+            };
+            let row = self.walls[(2 - next.1) as usize].as_bytes();
+            self.reply = Some(match row[(next.0 + 2) as usize] {
+                b'#' => 0,
+                b'O' => {
+                    self.pos = next;
+                    2
+                }
+                _ => {
+                    self.pos = next;
+                    1
+                }
+            });
+            Ok(Step::Continue)
+        }
+
+        fn push_input(&mut self, v: i128) {
+            self.pushed = Some(v);
+        }
+    }
+
+    #[test]
+    fn explores_maze() {
+        let mut droid = RepairDroid::new();
+        let mut maze = Maze {
+            walls: vec!["#####", "#.O.#", "#...#", "#.#.#", "#####"],
+            pos: (0, 0),
+            moves: 0,
+            reply: None,
+            pushed: None,
+        };
+
+        attach(&mut maze, &mut droid).unwrap();
+        assert_eq!(Some(&'O'), droid.map.get(&(0, 1)));
+        assert_eq!(Some(&'D'), droid.map.get(&(0, 0)));
+        assert_eq!(Some(&'#'), droid.map.get(&(0, -1)));
+        assert_eq!(8, droid.map.values().filter(|ch| **ch != '#').count());
+        assert_eq!((0, 0), droid.pos());
+        assert!(maze.moves > 0);
+    }
+}
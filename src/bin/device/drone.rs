@@ -0,0 +1,74 @@
+use super::{attach, Device};
+use crate::cpu::{Cpu, Image};
+use anyhow::Result;
+use std::collections::VecDeque;
+
+// day 19: sends a drone to (x, y) and reports whether the beam pulls it
+pub struct DroneProbe {
+    coords: VecDeque<i128>,
+    pub pulled: Option<bool>,
+}
+
+impl DroneProbe {
+    #[allow(dead_code)]
+    pub fn new(x: i128, y: i128) -> DroneProbe {
+        DroneProbe {
+            coords: vec![x, y].into_iter().collect(),
+            pulled: None,
+        }
+    }
+}
+
+impl Device for DroneProbe {
+    fn input(&mut self) -> Result<Option<i128>> {
+        Ok(self.coords.pop_front())
+    }
+
+    fn output(&mut self, value: i128) -> Result<()> {
+        self.pulled = match value {
+            0 => Some(false),
+            1 => Some(true),
+            _ => anyhow::bail!("invalid response: {}", value),
+        };
+        Ok(())
+    }
+}
+
+// every probe needs a fresh run of the program
+#[allow(dead_code)]
+pub fn probe(image: &Image, x: i128, y: i128) -> Result<bool> {
+    let mut drone = DroneProbe::new(x, y);
+    attach(&mut Cpu::new_detached(image), &mut drone)?;
+
+    drone
+        .pulled
+        .ok_or_else(|| anyhow::anyhow!("no response for ({}, {})", x, y))
+}
+
+#[cfg(test)]
+mod drone_tests {
+    use super::*;
+    use crate::device::{Event::*, Script};
+
+    #[test]
+    fn probes_point() {
+        let mut drone = DroneProbe::new(3, 4);
+        let mut script = Script::new(vec![Read, Read, Write(1)]);
+
+        attach(&mut script, &mut drone).unwrap();
+        assert_eq!(vec![3, 4], script.inputs);
+        assert_eq!(Some(true), drone.pulled);
+    }
+
+    #[test]
+    fn probe_program() {
+        // pulled when x == y
+        let image = Image::new(&[3, 11, 3, 12, 8, 11, 12, 13, 4, 13, 99]);
+        assert!(probe(&image, 2, 2).unwrap());
+        assert!(!probe(&image, 2, 3).unwrap());
+
+        let mut drone = DroneProbe::new(0, 0);
+        let mut script = Script::new(vec![Read, Read, Write(2)]);
+        assert!(attach(&mut script, &mut drone).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use super::Device;
+use crate::cpu::decoder::Decoder;
+use anyhow::Result;
+use std::collections::HashMap;
+
+// day 11: the camera reads the panel under the robot, the program answers
+// with (color to paint, turn to make) pairs
+pub struct HullPainter {
+    pub panels: HashMap<(isize, isize), u8>,
+    pos: (isize, isize),
+    direction: u8,
+    commands: Decoder<(u8, i128)>,
+}
+
+impl HullPainter {
+    #[allow(dead_code)]
+    pub fn new(start_panel: u8) -> HullPainter {
+        let mut panels = HashMap::new();
+        panels.insert((0, 0), start_panel);
+
+        HullPainter {
+            panels,
+            pos: (0, 0),
+            direction: 0,
+            commands: Decoder::new(2).rule(&[], |v| match v[0] {
+                0 | 1 => Ok((v[0] as u8, v[1])),
+                _ => anyhow::bail!("unexpected color: {}", v[0]),
+            }),
+        }
+    }
+
+    fn turn(&mut self, turn: i128) -> Result<()> {
+        self.direction = match turn {
+            0 => (self.direction + 3) % 4,
+            1 => (self.direction + 1) % 4,
+            _ => anyhow::bail!("unknown turn: {}", turn),
+        };
+
+        let (x, y) = self.pos;
+        self.pos = match self.direction {
+            0 => (x, y - 1),
+            1 => (x + 1, y),
+            2 => (x, y + 1),
+            _ => (x - 1, y),
+        };
+        Ok(())
+    }
+}
+
+impl Device for HullPainter {
+    fn input(&mut self) -> Result<Option<i128>> {
+        Ok(Some(*self.panels.get(&self.pos).unwrap_or(&0) as i128))
+    }
+
+    fn output(&mut self, value: i128) -> Result<()> {
+        if let Some(event) = self.commands.push(value)? {
+            let (color, turn) = event.record;
+            self.panels.insert(self.pos, color);
+            self.turn(turn)?;
+        }
+        Ok(())
+    }
+
+    fn halt(&mut self) -> Result<()> {
+        self.commands.finish()
+    }
+}
+
+#[cfg(test)]
+mod hull_tests {
+    use super::*;
+    use crate::device::{attach, Event::*, Script};
+
+    #[test]
+    fn paints_example() {
+        let mut robot = HullPainter::new(0);
+        let mut script = Script::new(vec![
+            Read,
+            Write(1),
+            Write(0),
+            Read,
+            Write(0),
+            Write(0),
+            Read,
+            Write(1),
+            Write(0),
+            Read,
+            Write(1),
+            Write(0),
+            Read,
+            Write(0),
+            Write(1),
+            Read,
+            Write(1),
+            Write(0),
+            Read,
+            Write(1),
+            Write(0),
+        ]);
+
+        attach(&mut script, &mut robot).unwrap();
+        assert_eq!(vec![0, 0, 0, 0, 1, 0, 0], script.inputs);
+        assert_eq!(6, robot.panels.len());
+        assert_eq!((0, -1), robot.pos);
+    }
+
+    #[test]
+    fn bad_color() {
+        let mut robot = HullPainter::new(1);
+        let mut script = Script::new(vec![Read, Write(7), Write(0)]);
+
+        assert_eq!(1, robot.input().unwrap().unwrap());
+        assert!(attach(&mut script, &mut robot).is_err());
+    }
+
+    #[test]
+    fn halts_between_commands() {
+        let mut robot = HullPainter::new(0);
+        let mut script = Script::new(vec![Read, Write(1)]);
+        assert!(attach(&mut script, &mut robot).is_err());
+    }
+}
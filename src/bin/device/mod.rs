@@ -0,0 +1,215 @@
+use crate::cpu::{Cpu, Step};
+use anyhow::Result;
+
+pub mod arcade;
+pub mod ascii;
+pub mod droid;
+pub mod drone;
+pub mod hull;
+
+// the side of the machine a device is plugged into
+pub trait Bus {
+    fn step(&mut self) -> Result<Step>;
+    fn push_input(&mut self, v: i128);
+}
+
+impl Bus for Cpu {
+    fn step(&mut self) -> Result<Step> {
+        Cpu::step(self)
+    }
+
+    fn push_input(&mut self, v: i128) {
+        Cpu::push_input(self, v)
+    }
+}
+
+pub trait Device {
+    // called whenever the program reads, `None` powers the machine down
+    fn input(&mut self) -> Result<Option<i128>>;
+
+    fn output(&mut self, value: i128) -> Result<()>;
+
+    // called once the program halted, e.g. to reject a half written record
+    fn halt(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+// runs the program until it halts or the device stops answering reads
+pub fn attach(bus: &mut impl Bus, device: &mut impl Device) -> Result<()> {
+    loop {
+        match bus.step()? {
+            Step::Continue => {}
+            Step::Output(value) => device.output(value)?,
+            Step::NeedInput => match device.input()? {
+                Some(value) => bus.push_input(value),
+                None => break,
+            },
+            Step::Halt => return device.halt(),
+        }
+    }
+    Ok(())
+}
+
+// Like `attach` for a cpu with mapped ports, the device at index n serves
+// port n. Inputs and outputs on any other port are an error.
+#[allow(dead_code)]
+pub fn attach_ports(cpu: &mut Cpu, devices: &mut [&mut dyn Device]) -> Result<()> {
+    loop {
+        let step = cpu.step()?;
+        let port = cpu.port();
+        let device = match step {
+            Step::Continue => continue,
+            Step::Halt => {
+                for device in devices.iter_mut() {
+                    device.halt()?;
+                }
+                break;
+            }
+            _ => match devices.get_mut(port as usize) {
+                Some(device) if port >= 0 => device,
+                _ => anyhow::bail!("no device on port {}", port),
+            },
+        };
+        match step {
+            Step::Output(value) => device.output(value)?,
+            _ => match device.input()? {
+                Some(value) => cpu.push_input(value),
+                None => break,
+            },
+        }
+    }
+    Ok(())
+}
+
+// a fake cpu playing back a fixed sequence of reads and writes
+#[cfg(test)]
+pub enum Event {
+    Read,
+    Write(i128),
+}
+
+#[cfg(test)]
+pub struct Script {
+    events: std::collections::VecDeque<Event>,
+    pushed: Option<i128>,
+    pub inputs: Vec<i128>,
+}
+
+#[cfg(test)]
+impl Script {
+    pub fn new(events: Vec<Event>) -> Script {
+        Script {
+            events: events.into_iter().collect(),
+            pushed: None,
+            inputs: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+impl Bus for Script {
+    fn step(&mut self) -> Result<Step> {
+        if let Some(value) = self.pushed.take() {
+            self.events.pop_front();
+            self.inputs.push(value);
+            return Ok(Step::Continue);
+        }
+
+        match self.events.front() {
+            Some(Event::Read) => Ok(Step::NeedInput),
+            Some(Event::Write(value)) => {
+                let value = *value;
+                self.events.pop_front();
+                Ok(Step::Output(value))
+            }
+            None => Ok(Step::Halt),
+        }
+    }
+
+    fn push_input(&mut self, v: i128) {
+        self.pushed = Some(v);
+    }
+}
+
+#[cfg(test)]
+mod device_tests {
+    use super::*;
+    use Event::*;
+
+    struct Echo {
+        reads: usize,
+        seen: Vec<i128>,
+    }
+
+    impl Device for Echo {
+        fn input(&mut self) -> Result<Option<i128>> {
+            self.reads += 1;
+            match self.reads {
+                1..=2 => Ok(Some(self.reads as i128 * 10)),
+                _ => Ok(None),
+            }
+        }
+
+        fn output(&mut self, value: i128) -> Result<()> {
+            self.seen.push(value);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn attach_script() {
+        let mut echo = Echo {
+            reads: 0,
+            seen: vec![],
+        };
+        let mut script = Script::new(vec![Read, Write(1), Read, Write(2), Read, Write(3)]);
+
+        attach(&mut script, &mut echo).unwrap();
+        assert_eq!(vec![10, 20], script.inputs);
+        assert_eq!(vec![1, 2], echo.seen);
+    }
+
+    #[test]
+    fn attach_cpu() {
+        let mut echo = Echo {
+            reads: 0,
+            seen: vec![],
+        };
+        // doubles every input, halts on a 0
+        let prog = vec![3, 20, 1006, 20, 14, 102, 2, 20, 20, 4, 20, 1105, 1, 0, 99];
+        let mut cpu = Cpu::new_detached(&prog);
+
+        attach(&mut cpu, &mut echo).unwrap();
+        assert_eq!(vec![20, 40], echo.seen);
+        assert_eq!(3, echo.reads);
+    }
+
+    #[test]
+    fn attach_ports_cpu() {
+        let mut display = Echo {
+            reads: 0,
+            seen: vec![],
+        };
+        let mut keyboard = Echo {
+            reads: 0,
+            seen: vec![],
+        };
+        // reads keys on port 1 and shows them doubled on port 0 until a 0
+        let prog = vec![
+            1101, 0, 1, 30, 3, 20, 1101, 0, 0, 30, 1006, 20, 22, 102, 2, 20, 20, 4, 20, 1105, 1, 0,
+            99,
+        ];
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.map_ports(30);
+
+        attach_ports(&mut cpu, &mut [&mut display, &mut keyboard]).unwrap();
+        assert_eq!(vec![20, 40], display.seen);
+        assert_eq!((0, 3), (display.reads, keyboard.reads));
+
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.map_ports(30);
+        let err = attach_ports(&mut cpu, &mut [&mut display]).unwrap_err();
+        assert_eq!("no device on port 1", err.to_string());
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+use cpu::dump::Dump;
+use cpu::Cpu;
+use intcode::cpu;
+
+// cargo run --bin dump -- resources/day13-input.txt 380 400 > before.dump
+// cargo run --bin dump -- --diff before.dump after.dump
+// prints the cells in start..end after running the program on the inputs,
+// or the cells that differ between two saved dumps
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 4 && args[1] == "--diff" {
+        let (before, after) = (Dump::load(&args[2])?, Dump::load(&args[3])?);
+        let changes = after.diff(&before);
+        for change in changes.iter() {
+            println!("{}: {} -> {}", change.addr, change.before, change.after);
+        }
+        println!("{} cells changed", changes.len());
+        return Ok(());
+    }
+    if args.len() < 4 {
+        anyhow::bail!(
+            "usage: dump <program> <start> <end> [inputs ...] | dump --diff <before> <after>"
+        );
+    }
+
+    let prog = cpu::parse_input(&args[1])?;
+    let (start, end) = (args[2].parse()?, args[3].parse()?);
+    let inputs: Vec<i128> = args[4..]
+        .iter()
+        .map(|v| v.parse())
+        .collect::<Result<_, _>>()?;
+
+    // runs until it halts or wants more than the inputs
+    let mut outputs = Cpu::new_detached(&prog).into_outputs(inputs);
+    outputs.by_ref().take_while(|v| v.is_ok()).for_each(drop);
+    print!("{}", outputs.into_cpu().dump(start, end).table());
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use intcode::cpu;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+mod cpu_async;
+mod fuzzer;
+
+// Runs random programs on both cpus and shrinks the first one they disagree
+// on. A seed repeats a run.
+//
+// cargo run --bin fuzz -- [programs] [seed]
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let programs: u64 = match args.get(1) {
+        Some(n) => n.parse()?,
+        None => 1000,
+    };
+    let seed: u64 = match args.get(2) {
+        Some(seed) => seed.parse()?,
+        None => rand::random(),
+    };
+
+    for n in 0..programs {
+        let seed = seed.wrapping_add(n);
+        let program = fuzzer::generate(&mut StdRng::seed_from_u64(seed));
+        let problem = match fuzzer::difference(&program) {
+            Some(problem) => problem,
+            None => continue,
+        };
+
+        println!("seed {}: {}", seed, problem);
+        let small = fuzzer::shrink(&program, |p| fuzzer::difference(p).is_some());
+        let cells: Vec<String> = small.assemble().iter().map(|v| v.to_string()).collect();
+        let inputs: Vec<String> = small.inputs.iter().map(|v| v.to_string()).collect();
+        println!("shrunk to: {}", fuzzer::difference(&small).unwrap());
+        println!("program: {}", cells.join(","));
+        println!("inputs: {}", inputs.join(","));
+        anyhow::bail!("the cpus disagree");
+    }
+    println!("{} programs from seed {}, no differences", programs, seed);
+    Ok(())
+}
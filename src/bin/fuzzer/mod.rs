@@ -0,0 +1,446 @@
+use crate::cpu::{Cpu, Step};
+use crate::cpu_async;
+use async_std::future;
+use async_std::task;
+use futures::sink;
+use futures::stream;
+use rand::{Rng, RngExt};
+use std::time::Duration;
+
+// the jump over the data, then the data and the loop counters
+const DATA_START: u128 = 3;
+const DATA: u128 = 16;
+const MAX_ITEMS: usize = 12;
+const MAX_DEPTH: usize = 2;
+const MAX_COUNT: i128 = 5;
+// the async cpu computes in 64 bits, programs with larger values are left out
+const MAX_VALUE: i128 = 1 << 40;
+const MAX_STEPS: usize = 100_000;
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param {
+    Pos(u128),
+    Imm(i128),
+    // an address, the offset from the relative base is worked out when
+    // assembling
+    Rel(u128),
+}
+
+use Param::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Item {
+    // 1, 2, 7 and 8 with two params and a destination, 3 with a
+    // destination and 4 with a param
+    Op(i128, Vec<Param>),
+    // 5 or 6 jumping over the next items of the block, never past a rebase
+    Skip(i128, Param, usize),
+    Rebase(i128),
+    // runs the body a number of times, the body leaves the relative base
+    // as it found it
+    Loop(i128, Vec<Item>),
+}
+
+// A program that only writes to its data and only jumps forward, except
+// for the loops. It ends in a halt or an unknown opcode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub data: Vec<i128>,
+    pub items: Vec<Item>,
+    pub end: i128,
+    pub inputs: Vec<i128>,
+}
+
+fn param(rng: &mut impl Rng, dest: bool) -> Param {
+    let addr = DATA_START + rng.random_range(0..DATA);
+    match rng.random_range(0..3) {
+        0 if !dest => Imm(rng.random_range(-20..=20)),
+        1 => Rel(addr),
+        _ => Pos(addr),
+    }
+}
+
+// `times` is how often the block runs at most, `reads` counts the inputs
+fn block(rng: &mut impl Rng, depth: usize, times: usize, reads: &mut usize) -> Vec<Item> {
+    let n = rng.random_range(1..=MAX_ITEMS);
+    (0..n).map(|_| item(rng, depth, times, reads)).collect()
+}
+
+fn item(rng: &mut impl Rng, depth: usize, times: usize, reads: &mut usize) -> Item {
+    match rng.random_range(0..10) {
+        0 if depth < MAX_DEPTH => {
+            let count = rng.random_range(1..=MAX_COUNT);
+            let body = block(rng, depth + 1, times * count as usize, reads);
+            Item::Loop(count, body)
+        }
+        1 => Item::Skip(
+            rng.random_range(5..=6),
+            param(rng, false),
+            rng.random_range(0..4),
+        ),
+        2 => Item::Rebase(rng.random_range(-8..=8)),
+        3 => {
+            *reads += times;
+            Item::Op(3, vec![param(rng, true)])
+        }
+        4 => Item::Op(4, vec![param(rng, false)]),
+        _ => {
+            let opcode = [1, 2, 7, 8][rng.random_range(0..4)];
+            let params = vec![param(rng, false), param(rng, false), param(rng, true)];
+            Item::Op(opcode, params)
+        }
+    }
+}
+
+pub fn generate(rng: &mut impl Rng) -> Program {
+    let mut reads = 0;
+    let items = block(rng, 0, 1, &mut reads);
+    let end = match rng.random_bool(0.1) {
+        true => rng.random_range(10..99) + 100 * rng.random_range(0..10),
+        false => 99,
+    };
+    Program {
+        data: (0..DATA).map(|_| rng.random_range(-50..=50)).collect(),
+        items,
+        end,
+        inputs: (0..reads).map(|_| rng.random_range(-100..=100)).collect(),
+    }
+}
+
+fn loops(items: &[Item]) -> usize {
+    items
+        .iter()
+        .map(|item| match item {
+            Item::Loop(_, body) => 1 + loops(body),
+            _ => 0,
+        })
+        .sum()
+}
+
+struct Assembler {
+    code: Vec<i128>,
+    // the relative base where the next instruction runs
+    base: i128,
+    counter: u128,
+}
+
+impl Assembler {
+    fn emit(&mut self, opcode: i128, params: &[Param]) {
+        let mut inst = opcode;
+        let mut cells = vec![];
+        for (n, p) in params.iter().enumerate() {
+            let (mode, v) = match *p {
+                Pos(addr) => (0, addr as i128),
+                Imm(v) => (1, v),
+                Rel(addr) => (2, addr as i128 - self.base),
+            };
+            inst += mode * 10i128.pow(n as u32 + 2);
+            cells.push(v);
+        }
+        self.code.push(inst);
+        self.code.extend(cells);
+    }
+
+    fn block(&mut self, items: &[Item]) {
+        // where each item starts, jumps are patched once all are known
+        let mut starts = vec![];
+        let mut jumps = vec![];
+        for (n, item) in items.iter().enumerate() {
+            starts.push(self.code.len());
+            match item {
+                Item::Op(opcode, params) => self.emit(*opcode, params),
+                Item::Skip(opcode, cond, over) => {
+                    let rebase = items[n + 1..]
+                        .iter()
+                        .position(|item| matches!(item, Item::Rebase(_)))
+                        .map_or(items.len(), |k| n + 1 + k);
+                    self.emit(*opcode, &[*cond, Imm(0)]);
+                    jumps.push((self.code.len() - 1, (n + 1 + over).min(rebase)));
+                }
+                Item::Rebase(k) => {
+                    self.emit(9, &[Imm(*k)]);
+                    self.base += k;
+                }
+                Item::Loop(count, body) => {
+                    let counter = Pos(self.counter);
+                    self.counter += 1;
+                    self.emit(1, &[Imm(*count), Imm(0), counter]);
+                    let (top, base) = (self.code.len(), self.base);
+                    self.block(body);
+                    if self.base != base {
+                        self.emit(9, &[Imm(base - self.base)]);
+                        self.base = base;
+                    }
+                    self.emit(1, &[counter, Imm(-1), counter]);
+                    self.emit(5, &[counter, Imm(top as i128)]);
+                }
+            }
+        }
+        starts.push(self.code.len());
+        for (cell, target) in jumps {
+            self.code[cell] = starts[target] as i128;
+        }
+    }
+}
+
+impl Program {
+    pub fn assemble(&self) -> Vec<i128> {
+        let counters = DATA_START + DATA;
+        let mut code = vec![1105, 1, 0];
+        code.extend(self.data.iter());
+        code.resize(counters as usize + loops(&self.items), 0);
+        code[2] = code.len() as i128;
+
+        let mut asm = Assembler {
+            code,
+            base: 0,
+            counter: counters,
+        };
+        asm.block(&self.items);
+        asm.code.push(self.end);
+        asm.code
+    }
+
+    // every way to take a bit away from the program
+    fn smaller(&self) -> Vec<Program> {
+        let mut out: Vec<Program> = smaller(&self.items)
+            .into_iter()
+            .map(|items| Program {
+                items,
+                ..self.clone()
+            })
+            .collect();
+        if self.end != 99 {
+            out.push(Program {
+                end: 99,
+                ..self.clone()
+            });
+        }
+        if !self.inputs.is_empty() {
+            let mut p = self.clone();
+            p.inputs.pop();
+            out.push(p);
+        }
+        for k in 0..self.data.len() {
+            if self.data[k] != 0 {
+                let mut p = self.clone();
+                p.data[k] = 0;
+                out.push(p);
+            }
+        }
+        out
+    }
+}
+
+fn smaller(items: &[Item]) -> Vec<Vec<Item>> {
+    let mut out = vec![];
+    for n in 0..items.len() {
+        let mut without = items.to_vec();
+        without.remove(n);
+        out.push(without);
+
+        if let Item::Loop(count, body) = &items[n] {
+            let mut inline = items.to_vec();
+            inline.splice(n..=n, body.iter().cloned());
+            out.push(inline);
+            if *count > 1 {
+                let mut once = items.to_vec();
+                once[n] = Item::Loop(1, body.clone());
+                out.push(once);
+            }
+            for body in smaller(body) {
+                let mut v = items.to_vec();
+                v[n] = Item::Loop(*count, body);
+                out.push(v);
+            }
+        }
+    }
+    out
+}
+
+// how a run went on one of the cpus, with the memory as long as the program
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outcome {
+    pub outputs: Vec<i128>,
+    pub memory: Vec<i128>,
+    pub error: Option<String>,
+}
+
+// None when the program runs out of inputs, steps or bounds
+pub fn run_sync(prog: &[i128], inputs: &[i128]) -> Option<Outcome> {
+    let mut cpu = Cpu::new_detached(prog);
+    for v in inputs.iter() {
+        cpu.push_input(*v);
+    }
+    let mut outcome = Outcome::default();
+    let mut steps = 0;
+    loop {
+        steps += 1;
+        if steps > MAX_STEPS {
+            return None;
+        }
+        match cpu.step() {
+            Ok(Step::Continue) => {}
+            Ok(Step::Output(v)) => outcome.outputs.push(v),
+            Ok(Step::NeedInput) => return None,
+            Ok(Step::Halt) => break,
+            Err(e) => {
+                outcome.error = Some(e.to_string());
+                break;
+            }
+        }
+        let data = DATA_START..DATA_START + DATA;
+        if data
+            .map(|k| cpu.get_mem(k).unwrap())
+            .any(|v| v.abs() > MAX_VALUE)
+        {
+            return None;
+        }
+    }
+    let len = prog.len() as u128;
+    outcome.memory = (0..len).map(|k| cpu.get_mem(k).unwrap()).collect();
+    Some(outcome)
+}
+
+pub fn run_async(prog: &[i128], inputs: &[i128]) -> Outcome {
+    let cells: Vec<isize> = prog.iter().map(|v| *v as isize).collect();
+    let inputs: Vec<isize> = inputs.iter().map(|v| *v as isize).collect();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let output = sink::unfold(tx, |tx, v: isize| async move {
+        tx.send(v as i128)?;
+        Ok::<_, anyhow::Error>(tx)
+    });
+
+    let mut cpu = cpu_async::Cpu::from_io(&cells, stream::iter(inputs), output);
+    let error = match task::block_on(future::timeout(TIMEOUT, cpu.execute())) {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some("timed out".to_string()),
+    };
+    Outcome {
+        outputs: rx.try_iter().collect(),
+        memory: (0..prog.len())
+            .map(|k| cpu.prog.get(k).map_or(0, |v| *v as i128))
+            .collect(),
+        error,
+    }
+}
+
+// how the cpus disagree on the program, None when they don't or when the
+// program is out of bounds on the sync cpu
+pub fn difference(program: &Program) -> Option<String> {
+    let prog = program.assemble();
+    let sync = run_sync(&prog, &program.inputs)?;
+    let other = run_async(&prog, &program.inputs);
+    if sync.outputs != other.outputs {
+        return Some(format!(
+            "outputs {:?} and {:?}",
+            sync.outputs, other.outputs
+        ));
+    }
+    if sync.error != other.error {
+        return Some(format!("errors {:?} and {:?}", sync.error, other.error));
+    }
+    let addr = (0..prog.len()).find(|k| sync.memory[*k] != other.memory[*k])?;
+    Some(format!(
+        "memory at {}: {} and {}",
+        addr, sync.memory[addr], other.memory[addr]
+    ))
+}
+
+// takes parts away from the program for as long as it still fails
+pub fn shrink<F>(program: &Program, fails: F) -> Program
+where
+    F: Fn(&Program) -> bool,
+{
+    let mut program = program.clone();
+    while let Some(p) = program.smaller().into_iter().find(|p| fails(p)) {
+        program = p;
+    }
+    program
+}
+
+#[cfg(test)]
+mod fuzzer_tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn outputs(program: &Program) -> Vec<i128> {
+        run_sync(&program.assemble(), &program.inputs)
+            .unwrap()
+            .outputs
+    }
+
+    #[test]
+    fn assembly() {
+        let mut data = vec![0; DATA as usize];
+        data[0] = 1;
+        let program = Program {
+            data,
+            items: vec![
+                // [3] += 2 three times, moving the relative base along
+                Item::Loop(
+                    3,
+                    vec![Item::Op(1, vec![Rel(3), Imm(2), Rel(3)]), Item::Rebase(5)],
+                ),
+                Item::Op(4, vec![Rel(3)]),
+                // [4] is 0, the jump skips the output of 8 but not the rebase
+                Item::Skip(6, Pos(4), 3),
+                Item::Op(4, vec![Imm(8)]),
+                Item::Rebase(-1),
+                Item::Op(4, vec![Rel(3)]),
+                Item::Op(3, vec![Pos(5)]),
+                Item::Op(4, vec![Pos(5)]),
+            ],
+            end: 99,
+            inputs: vec![42],
+        };
+        assert_eq!(vec![7, 7, 42], outputs(&program));
+        assert_eq!(None, difference(&program));
+
+        let program = Program {
+            end: 1234,
+            ..program
+        };
+        let prog = program.assemble();
+        let error = run_sync(&prog, &program.inputs).unwrap().error;
+        let at = prog.len() - 1;
+        assert_eq!(Some(format!("unknown opcode '1234' at '{}'", at)), error);
+        assert_eq!(None, difference(&program));
+    }
+
+    #[test]
+    fn cpus_agree() {
+        let mut bounded = 0;
+        for seed in 0..200 {
+            let program = generate(&mut StdRng::seed_from_u64(seed));
+            if run_sync(&program.assemble(), &program.inputs).is_some() {
+                bounded += 1;
+            }
+            assert_eq!(None, difference(&program), "seed {}", seed);
+        }
+        assert!(bounded > 150, "{}", bounded);
+    }
+
+    #[test]
+    fn shrinks() {
+        // stands in for a difference, at least 3 outputs
+        let fails = |p: &Program| match run_sync(&p.assemble(), &p.inputs) {
+            Some(outcome) => outcome.outputs.len() >= 3,
+            None => false,
+        };
+        let program = (0..)
+            .map(|seed| generate(&mut StdRng::seed_from_u64(seed)))
+            .find(|p| fails(p) && p.items.len() > 5)
+            .unwrap();
+
+        let small = shrink(&program, fails);
+        assert!(fails(&small));
+        assert!(small.items.len() <= 3, "{:?}", small.items);
+        assert!(small.data.iter().all(|v| *v == 0));
+        assert_eq!(99, small.end);
+        assert!(small.assemble().len() < program.assemble().len());
+    }
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use intcode::cpu;
+use std::net::TcpListener;
+
+// cargo run --bin gdbserver -- resources/day9-input.txt 1234
+// serves a program to one gdb client, then `target remote :1234` in gdb.
+// inputs are given with `monitor input 1 2 3`
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: gdbserver <program> [port]");
+    }
+    let port = match args.get(2) {
+        Some(port) => port.parse()?,
+        None => 1234u16,
+    };
+
+    let prog = cpu::parse_input(&args[1])?;
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("listening on {}", listener.local_addr()?);
+    let mut stub = cpu::gdb::Stub::new(cpu::Cpu::new_detached(&prog));
+    stub.serve(&listener)?;
+
+    Ok(())
+}
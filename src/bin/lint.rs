@@ -0,0 +1,22 @@
+use anyhow::Result;
+use intcode::cpu;
+
+// cargo run --bin lint -- resources/day9-input.txt
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: lint <program>");
+    }
+
+    let prog = cpu::parse_input(&args[1])?;
+    let lints = cpu::lint::lint(&prog);
+    for lint in lints.iter() {
+        println!("{}: {}", lint.pc, lint.problem);
+    }
+
+    if !lints.is_empty() {
+        anyhow::bail!("{} problems", lints.len());
+    }
+    println!("no problems");
+    Ok(())
+}
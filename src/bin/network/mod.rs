@@ -0,0 +1,318 @@
+use crate::cpu_async::{sender_sink, Cpu};
+use anyhow::Result;
+use async_std::prelude::*;
+use async_std::sync::{channel, Receiver, Sender};
+use async_std::task::{self, JoinHandle};
+use futures::channel::mpsc::{self, UnboundedSender};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const NAT: usize = 255;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    pub src: usize,
+    pub dest: usize,
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    routed: AtomicUsize,
+    nat: AtomicUsize,
+    dropped: AtomicUsize,
+    crashed: AtomicUsize,
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    pub nodes: usize,
+    pub routed: usize,
+    pub nat: usize,
+    pub dropped: usize,
+    pub crashed: usize,
+    pub elapsed: Duration,
+}
+
+impl Counters {
+    fn metrics(&self, nodes: usize, elapsed: Duration) -> Metrics {
+        Metrics {
+            nodes,
+            routed: self.routed.load(Ordering::Relaxed),
+            nat: self.nat.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            crashed: self.crashed.load(Ordering::Relaxed),
+            elapsed,
+        }
+    }
+}
+
+impl Metrics {
+    // packets per second, counting every packet that left a nic
+    pub fn throughput(&self) -> f64 {
+        let total = (self.routed + self.nat + self.dropped) as f64;
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => total / secs,
+            _ => 0.0,
+        }
+    }
+}
+
+// Every nic runs as a task instead of a thread. Packets travel over bounded
+// channels and a router waits while the inbox or nat it sends to is full.
+// Deliverers never wait, they hand packets to an unbounded nic input, so a
+// nic waiting on its own output can't close a cycle of nics, routers and
+// deliverers that all wait on each other.
+pub struct Network {
+    nodes: usize,
+    stop: Sender<()>,
+    inputs: Vec<UnboundedSender<isize>>,
+    nat: Receiver<Packet>,
+    handles: Vec<JoinHandle<Result<()>>>,
+    counters: Arc<Counters>,
+    started: Instant,
+}
+
+async fn stopped(stop: Receiver<()>) -> Result<()> {
+    // nothing is ever sent, recv returns once the network drops its sender
+    let _ = stop.recv().await;
+    Ok(())
+}
+
+async fn route(
+    src: usize,
+    output: Receiver<isize>,
+    inboxes: Arc<Vec<Sender<Packet>>>,
+    nat: Sender<Packet>,
+    counters: Arc<Counters>,
+) -> Result<()> {
+    loop {
+        // the nic halted, nothing left to route
+        let dest = match output.recv().await {
+            Ok(dest) => dest,
+            Err(_) => return Ok(()),
+        };
+        let x = output.recv().await?;
+        let y = output.recv().await?;
+        let packet = Packet {
+            src,
+            dest: dest as usize,
+            x,
+            y,
+        };
+
+        let counter = match dest as usize {
+            NAT => {
+                nat.send(packet).await;
+                &counters.nat
+            }
+            n if dest >= 0 && n < inboxes.len() => {
+                inboxes[n].send(packet).await;
+                &counters.routed
+            }
+            _ => &counters.dropped,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// a single writer per nic keeps the x and y of a packet together. packets
+// for a nic that halted are thrown away, routers never wait on it
+async fn deliver(inbox: Receiver<Packet>, input: UnboundedSender<isize>) -> Result<()> {
+    while let Ok(packet) = inbox.recv().await {
+        let _ = input.unbounded_send(packet.x);
+        let _ = input.unbounded_send(packet.y);
+    }
+    Ok(())
+}
+
+impl Network {
+    pub async fn boot(prog: &[isize], nodes: usize, capacity: usize) -> Result<Network> {
+        anyhow::ensure!(capacity > 0, "capacity must be positive");
+        let (stop, stop_rx) = channel::<()>(1);
+        let (nat_tx, nat) = channel::<Packet>(capacity);
+        let counters = Arc::new(Counters::default());
+        let mut handles = vec![];
+        let mut inputs = vec![];
+        let mut inboxes = vec![];
+        let mut outputs = vec![];
+
+        for addr in 0..nodes {
+            let (inbox_tx, inbox_rx) = channel::<Packet>(capacity);
+            let (input_tx, input_rx) = mpsc::unbounded::<isize>();
+            let (output_tx, output_rx) = channel::<isize>(capacity * 3);
+
+            input_tx.unbounded_send(addr as isize)?;
+
+            // a crashing nic is counted, the rest of the network keeps going
+            let mut cpu = Cpu::from_io(prog, input_rx, sender_sink(output_tx)).with_idle_input(-1);
+            let stop = stop_rx.clone();
+            let node_counters = counters.clone();
+            handles.push(task::spawn(async move {
+                if cpu.execute().race(stopped(stop)).await.is_err() {
+                    node_counters.crashed.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(())
+            }));
+
+            let stop = stop_rx.clone();
+            let deliverer = deliver(inbox_rx, input_tx.clone());
+            handles.push(task::spawn(
+                async move { deliverer.race(stopped(stop)).await },
+            ));
+
+            inputs.push(input_tx);
+            inboxes.push(inbox_tx);
+            outputs.push(output_rx);
+        }
+
+        let inboxes = Arc::new(inboxes);
+        for (src, output) in outputs.into_iter().enumerate() {
+            let stop = stop_rx.clone();
+            let router = route(
+                src,
+                output,
+                inboxes.clone(),
+                nat_tx.clone(),
+                counters.clone(),
+            );
+            handles.push(task::spawn(async move { router.race(stopped(stop)).await }));
+        }
+
+        Ok(Network {
+            nodes,
+            stop,
+            inputs,
+            nat,
+            handles,
+            counters,
+            started: Instant::now(),
+        })
+    }
+
+    // packets sent to address 255, routers wait once this fills up
+    pub fn nat(&self) -> &Receiver<Packet> {
+        &self.nat
+    }
+
+    #[allow(dead_code)]
+    pub fn metrics(&self) -> Metrics {
+        self.counters.metrics(self.nodes, self.started.elapsed())
+    }
+
+    pub async fn shutdown(self) -> Result<Metrics> {
+        let elapsed = self.started.elapsed();
+        drop(self.stop);
+        drop(self.nat);
+
+        for handle in self.handles {
+            handle.await?;
+        }
+        // an idle nic treats a closed input as an error, so close them last
+        drop(self.inputs);
+
+        Ok(self.counters.metrics(self.nodes, elapsed))
+    }
+}
+
+#[cfg(test)]
+mod network_tests {
+    use super::*;
+
+    // reads its address and sends (address + 1, address, 0) to the next node,
+    // then forwards every packet it gets to the next node with y + 1
+    const CHAIN: [isize; 36] = [
+        3, 100, 1001, 100, 1, 101, 4, 101, 4, 100, 104, 0, 3, 102, 1008, 102, -1, 103, 1005, 103,
+        12, 3, 104, 4, 101, 4, 102, 1001, 104, 1, 104, 4, 104, 1105, 1, 12,
+    ];
+
+    #[async_std::test]
+    async fn routes_to_nat() {
+        // node 0 sends straight to the nat
+        let prog = vec![3, 9, 104, 255, 104, 7, 104, 42, 99, 0];
+        let net = Network::boot(&prog, 3, 1).await.unwrap();
+
+        let packet = net.nat().recv().await.unwrap();
+        assert_eq!(255, packet.dest);
+        assert_eq!((7, 42), (packet.x, packet.y));
+
+        let metrics = net.shutdown().await.unwrap();
+        assert_eq!(3, metrics.nodes);
+        assert!(metrics.nat >= 1);
+    }
+
+    #[async_std::test]
+    async fn drops_unknown_addresses() {
+        let prog = vec![104, 5000, 104, 1, 104, 2, 99];
+        let net = Network::boot(&prog, 2, 1).await.unwrap();
+
+        while net.metrics().dropped < 2 {
+            task::sleep(Duration::from_millis(1)).await;
+        }
+
+        let metrics = net.shutdown().await.unwrap();
+        assert_eq!(2, metrics.dropped);
+        assert_eq!(0, metrics.routed);
+        assert_eq!(0, metrics.crashed);
+    }
+
+    #[async_std::test]
+    async fn counts_crashed_nodes() {
+        let prog = vec![3, 8, 1005, 8, 7, 99, 0, 42, 0];
+        let net = Network::boot(&prog, 3, 1).await.unwrap();
+
+        while net.metrics().crashed < 2 {
+            task::sleep(Duration::from_millis(1)).await;
+        }
+
+        let metrics = net.shutdown().await.unwrap();
+        assert_eq!(2, metrics.crashed);
+    }
+
+    #[async_std::test]
+    async fn waits_on_full_inboxes() {
+        // every node sends 50 packets to node 0, then keeps reading
+        let prog = vec![
+            104, 0, 104, 1, 104, 2, 1001, 20, -1, 20, 1005, 20, 0, 3, 21, 1105, 1, 13, 0, 0, 50, 0,
+        ];
+        let net = Network::boot(&prog, 5, 1).await.unwrap();
+
+        while net.metrics().routed < 250 {
+            task::sleep(Duration::from_millis(1)).await;
+        }
+
+        let metrics = net.shutdown().await.unwrap();
+        assert_eq!(250, metrics.routed);
+        assert_eq!(0, metrics.dropped);
+    }
+
+    #[async_std::test]
+    async fn many_nodes_without_threads() {
+        let net = Network::boot(&CHAIN, 2000, 4).await.unwrap();
+
+        while net.metrics().routed < 2000 {
+            task::sleep(Duration::from_millis(5)).await;
+        }
+
+        let metrics = net.shutdown().await.unwrap();
+        assert!(metrics.routed >= 2000);
+    }
+
+    #[async_std::test]
+    async fn day23_on_many_nodes() {
+        // the program only knows addresses below 50, the others crash, spin or
+        // halt. none of them may hold up the nodes that do work
+        let prog = crate::cpu_async::parse_input("resources/day23-input.txt").unwrap();
+        let net = Network::boot(&prog, 1000, 16).await.unwrap();
+
+        // which nic reaches the nat first depends on the scheduling
+        let nat = net.nat().recv().await.unwrap();
+        assert!(nat.src < 50);
+
+        let metrics = net.shutdown().await.unwrap();
+        assert!(metrics.routed > 0);
+        assert!(metrics.crashed <= 950, "{:?}", metrics);
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::Result;
+use intcode::cpu;
+mod optimizer;
+
+// cargo run --bin optimize -- resources/day2-input.txt [output] [1,2]
+// the last argument lists cells set before the run, like the noun and verb
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: optimize <program> [output] [patched cells]");
+    }
+
+    let prog = cpu::parse_input(&args[1])?;
+    let patched = match args.get(3) {
+        Some(cells) => cells
+            .split(',')
+            .map(|k| k.trim().parse())
+            .collect::<Result<Vec<usize>, _>>()?,
+        None => vec![],
+    };
+    let (optimized, report) = optimizer::optimize(&prog, &patched);
+
+    match report.skipped {
+        Some(reason) => println!("left unchanged: {}", reason),
+        None => println!(
+            "folded: {} jumps: {} blanked: {}",
+            report.folded, report.jumps, report.blanked
+        ),
+    }
+    if let Some(reason) = report.incomplete {
+        println!("unreachable code kept: {}", reason);
+    }
+
+    if let Some(path) = args.get(2) {
+        let cells: Vec<String> = optimized.iter().map(|v| v.to_string()).collect();
+        std::fs::write(path, cells.join(","))?;
+    }
+
+    Ok(())
+}
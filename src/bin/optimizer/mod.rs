@@ -0,0 +1,592 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub folded: usize,
+    pub jumps: usize,
+    pub blanked: usize,
+    // why the program was left as it is
+    pub skipped: Option<String>,
+    // why code that looks unreachable was kept
+    pub incomplete: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Inst {
+    opcode: i128,
+    modes: [i128; 3],
+    len: usize,
+}
+
+fn decode(v: i128) -> Option<Inst> {
+    if v < 0 {
+        return None;
+    }
+    let modes = [v / 100 % 10, v / 1000 % 10, v / 10000 % 10];
+    let len = match v % 100 {
+        1 | 2 | 7 | 8 => 4,
+        5 | 6 => 3,
+        3 | 4 | 9 => 2,
+        99 => 1,
+        _ => return None,
+    };
+    if v / 100000 != 0 || modes.iter().any(|m| *m > 2) {
+        return None;
+    }
+    Some(Inst {
+        opcode: v % 100,
+        modes,
+        len,
+    })
+}
+
+fn writes(inst: &Inst) -> Option<usize> {
+    match inst.opcode {
+        1 | 2 | 7 | 8 => Some(3),
+        3 => Some(1),
+        _ => None,
+    }
+}
+
+// every cell an instruction can touch, found by walking the code from 0
+#[derive(Default)]
+struct Flow {
+    code: BTreeSet<usize>,
+    starts: BTreeSet<usize>,
+    reads: HashSet<usize>,
+    writes: HashSet<usize>,
+    // how many instructions of the walk cover a cell
+    cover: HashMap<usize, usize>,
+    // cells of instructions the program writes over
+    kept: HashSet<usize>,
+    // a jump the walk couldn't follow, the code it missed is kept
+    incomplete: Option<String>,
+}
+
+struct Program<'a> {
+    prog: &'a [i128],
+    written: &'a HashSet<usize>,
+    // constant conditions prune the walk
+    prune: bool,
+}
+
+impl<'a> Program<'a> {
+    fn get(&self, k: usize) -> i128 {
+        *self.prog.get(k).unwrap_or(&0)
+    }
+
+    // the value of a parameter if no run can change it
+    fn constant(&self, pc: usize, inst: &Inst, n: usize) -> Option<i128> {
+        if self.written.contains(&(pc + n)) {
+            return None;
+        }
+        let raw = self.get(pc + n);
+        match inst.modes[n - 1] {
+            1 => Some(raw),
+            0 if raw >= 0 && !self.written.contains(&(raw as usize)) => {
+                Some(self.get(raw as usize))
+            }
+            _ => None,
+        }
+    }
+
+    // where a jump goes, `None` when the program computes it
+    fn target(&self, pc: usize, mode: i128) -> Option<usize> {
+        if self.written.contains(&(pc + 2)) {
+            return None;
+        }
+        let target = match mode {
+            1 => self.get(pc + 2),
+            0 if !self.written.contains(&(self.get(pc + 2).max(0) as usize)) => {
+                self.get(self.get(pc + 2).max(0) as usize)
+            }
+            _ => return None,
+        };
+        if target < 0 {
+            return None;
+        }
+        Some(target as usize)
+    }
+
+    fn successors(&self, pc: usize, inst: &Inst, flow: &mut Flow) -> Vec<usize> {
+        let next = pc + inst.len;
+        match inst.opcode {
+            99 => vec![],
+            5 | 6 => {
+                let cond = match self.prune {
+                    true => self.constant(pc, inst, 1),
+                    false => None,
+                };
+                let taken = cond.map(|cond| (cond != 0) == (inst.opcode == 5));
+                let mut successors = match taken {
+                    Some(true) => vec![],
+                    _ => vec![next],
+                };
+                if taken != Some(false) {
+                    match self.target(pc, inst.modes[1]) {
+                        Some(target) => successors.push(target),
+                        None => flow.incomplete = Some(format!("computed jump at {}", pc)),
+                    }
+                }
+                successors
+            }
+            _ => vec![next],
+        }
+    }
+
+    // A call stores the address after its jump and jumps, the return comes
+    // back there through the stack. Returns are computed jumps, so the
+    // walk goes on after the call instead.
+    fn returns_to(&self, pc: usize, inst: &Inst) -> Option<usize> {
+        if inst.opcode != 1 || inst.modes[..2] != [1, 1] {
+            return None;
+        }
+        if (1..3).any(|n| self.written.contains(&(pc + n))) {
+            return None;
+        }
+        let jump = pc + inst.len;
+        match decode(self.get(jump)) {
+            Some(next) if next.opcode == 5 || next.opcode == 6 => {}
+            _ => return None,
+        }
+        let ret = self.get(pc + 1) + self.get(pc + 2);
+        match ret == (jump + 3) as i128 {
+            true => Some(ret as usize),
+            false => None,
+        }
+    }
+
+    // The program writes the instruction at `pc`, which can then be any of
+    // them. Its cells are kept and every way to read it is followed.
+    fn computed(&self, pc: usize, flow: &mut Flow, todo: &mut Vec<usize>) -> Result<(), String> {
+        if (1..4).any(|n| self.written.contains(&(pc + n))) {
+            return Err(format!("computed instruction at {}", pc));
+        }
+        flow.kept.extend(pc..pc + 4);
+        flow.code.extend(pc..pc + 4);
+        for n in 1..4 {
+            *flow.cover.entry(pc + n).or_default() += 1;
+        }
+        let (a, b, c) = (self.get(pc + 1), self.get(pc + 2), self.get(pc + 3));
+        flow.reads
+            .extend([a, b].iter().filter(|v| **v >= 0).map(|v| *v as usize));
+        flow.writes
+            .extend([a, c].iter().filter(|v| **v >= 0).map(|v| *v as usize));
+
+        todo.extend(pc + 2..pc + 5);
+        for mode in 0..3 {
+            match self.target(pc, mode) {
+                Some(target) => todo.push(target),
+                None => flow.incomplete = Some(format!("computed jump at {}", pc)),
+            }
+        }
+        Ok(())
+    }
+
+    // Relative accesses are taken to stay on a stack past the image, which
+    // is how the programs use them. The walk checks that every path sets
+    // the base there with a `109` before it uses the stack.
+    fn flow(&self) -> Result<Flow, String> {
+        let mut flow = Flow::default();
+        let mut seen = HashSet::new();
+
+        // the first instruction runs before the program writes anything
+        let mut fresh = self.written.clone();
+        let mut todo = vec![];
+        if let Some(inst) = decode(self.get(0)) {
+            for k in 0..inst.len {
+                fresh.remove(&k);
+            }
+        }
+        Program {
+            prog: self.prog,
+            written: &fresh,
+            prune: self.prune,
+        }
+        .visit(0, false, &mut flow, &mut todo)?;
+
+        while let Some((pc, stack)) = todo.pop() {
+            if seen.insert((pc, stack)) {
+                self.visit(pc, stack, &mut flow, &mut todo)?;
+            }
+        }
+
+        Ok(flow)
+    }
+
+    fn visit(
+        &self,
+        pc: usize,
+        stack: bool,
+        flow: &mut Flow,
+        todo: &mut Vec<(usize, bool)>,
+    ) -> Result<(), String> {
+        flow.starts.insert(pc);
+        let mut next = vec![];
+        if self.written.contains(&pc) {
+            self.computed(pc, flow, &mut next)?;
+            todo.extend(next.into_iter().map(|k| (k, stack)));
+            return Ok(());
+        }
+        // a cpu getting here crashes, the cell is kept as it is
+        let inst = match decode(self.get(pc)) {
+            Some(inst) if pc + inst.len <= self.prog.len() => inst,
+            _ => {
+                flow.code.insert(pc);
+                return Ok(());
+            }
+        };
+        flow.code.extend(pc..pc + inst.len);
+        for k in pc..pc + inst.len {
+            *flow.cover.entry(k).or_default() += 1;
+        }
+
+        let sets_stack =
+            inst.opcode == 9 && inst.modes[0] == 1 && self.get(pc + 1) >= self.prog.len() as i128;
+        let relative = inst.opcode == 9 || inst.modes[..inst.len - 1].contains(&2);
+        if relative && !stack && !sets_stack {
+            return Err(format!(
+                "relative access at {} before the stack is set up",
+                pc
+            ));
+        }
+
+        for n in 1..inst.len {
+            let raw = self.get(pc + n);
+            let write = writes(&inst) == Some(n);
+            match inst.modes[n - 1] {
+                2 => continue,
+                1 if !write => continue,
+                _ => {}
+            }
+            if self.written.contains(&(pc + n)) {
+                return Err(match write {
+                    true => format!("write through the address at {}", pc + n),
+                    false => format!("read through the address at {}", pc + n),
+                });
+            }
+            if raw < 0 {
+                return Err(format!("negative address at {}", pc));
+            }
+            if write {
+                flow.writes.insert(raw as usize);
+            } else {
+                flow.reads.insert(raw as usize);
+            }
+        }
+        if inst.opcode == 9 && inst.modes[0] != 1 {
+            return Err(format!("relative base set from memory at {}", pc));
+        }
+
+        next.extend(self.successors(pc, &inst, flow));
+        next.extend(self.returns_to(pc, &inst));
+        todo.extend(next.into_iter().map(|k| (k, stack || sets_stack)));
+        Ok(())
+    }
+}
+
+// The walk of the whole program, every branch taken. What it finds written
+// can make more cells written, so it goes until that settles.
+fn walk(prog: &[i128], patched: &[usize]) -> Result<(Flow, HashSet<usize>), String> {
+    let mut written: HashSet<usize> = patched.iter().copied().collect();
+    loop {
+        let flow = Program {
+            prog,
+            written: &written,
+            prune: false,
+        }
+        .flow()?;
+        let more: HashSet<usize> = flow.writes.union(&written).copied().collect();
+        if more == written {
+            return Ok((flow, written));
+        }
+        written = more;
+    }
+}
+
+// Rewrites a program without changing what it does. Arithmetic and
+// comparisons on constants become `1101 value 0 dest`, jumps that are
+// always taken become unconditional and code that can't be reached is
+// zeroed. Written cells, the instructions the program writes over and the
+// cells `patched` before the run are data and stay as they are. Code past
+// a jump the program computes isn't known, then nothing is zeroed and the
+// report says why. Programs that read or write through computed addresses
+// could touch any cell and are left alone with the reason in
+// `Report::skipped`.
+pub fn optimize(prog: &[i128], patched: &[usize]) -> (Vec<i128>, Report) {
+    let mut report = Report::default();
+
+    let (flow, written) = match walk(prog, patched) {
+        Ok(walk) => walk,
+        Err(reason) => {
+            report.skipped = Some(reason);
+            return (prog.to_vec(), report);
+        }
+    };
+
+    // with the written cells known, constant conditions prune the walk
+    let second = Program {
+        prog,
+        written: &written,
+        prune: true,
+    };
+    let reached = match second.flow() {
+        Ok(reached) => reached,
+        Err(reason) => {
+            report.skipped = Some(reason);
+            return (prog.to_vec(), report);
+        }
+    };
+
+    let mut out = prog.to_vec();
+    let data = |k: &usize| flow.reads.contains(k) || written.contains(k) || flow.kept.contains(k);
+    // cells of a single instruction that nothing else reads or writes
+    let untouched = |cells: &[usize]| {
+        cells
+            .iter()
+            .all(|k| !data(k) && flow.cover.get(k) == Some(&1))
+    };
+
+    for &pc in reached.starts.iter() {
+        let inst = match decode(second.get(pc)) {
+            Some(inst) if pc + inst.len <= prog.len() => inst,
+            _ => continue,
+        };
+        match inst.opcode {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = match (second.constant(pc, &inst, 1), second.constant(pc, &inst, 2)) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => continue,
+                };
+                let value = match inst.opcode {
+                    1 => a + b,
+                    2 => a * b,
+                    7 => (a < b) as i128,
+                    _ => (a == b) as i128,
+                };
+                let folded = [1101 + inst.modes[2] * 10000, value, 0];
+                if out[pc..pc + 3] != folded && untouched(&[pc, pc + 1, pc + 2]) {
+                    out[pc..pc + 3].copy_from_slice(&folded);
+                    report.folded += 1;
+                }
+            }
+            5 | 6 => {
+                let taken = match second.constant(pc, &inst, 1) {
+                    Some(cond) => (cond != 0) == (inst.opcode == 5),
+                    None => continue,
+                };
+                let jump = [105 + inst.modes[1] * 1000, 1];
+                if taken && out[pc..pc + 2] != jump && untouched(&[pc, pc + 1]) {
+                    out[pc..pc + 2].copy_from_slice(&jump);
+                    report.jumps += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(reason) = flow.incomplete {
+        report.incomplete = Some(reason);
+        return (out, report);
+    }
+    for (k, cell) in out.iter_mut().enumerate() {
+        if !reached.code.contains(&k) && !data(&k) && *cell != 0 {
+            *cell = 0;
+            report.blanked += 1;
+        }
+    }
+
+    (out, report)
+}
+
+#[cfg(test)]
+mod optimizer_tests {
+    use super::*;
+    use crate::cpu;
+
+    #[test]
+    fn folds_constants() {
+        // mem[11] = 2 + 3, out(mem[9] * 7) where mem[9] is never written
+        let prog = vec![1101, 2, 3, 11, 1002, 9, 7, 12, 4, 12, 99, 0, 0];
+        let (out, report) = optimize(&prog, &[]);
+        assert_eq!(None, report.skipped);
+        assert_eq!(2, report.folded);
+        assert_eq!(vec![1101, 5, 0, 11, 1101, 84, 0, 12], out[..8].to_vec());
+        assert_eq!(
+            cpu::run_with_inputs(&prog, &[]).unwrap(),
+            cpu::run_with_inputs(&out, &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn jumps_and_dead_code() {
+        // jumps over 3 cells of junk when mem[11] != 0, then echoes an input
+        let prog = vec![1005, 11, 6, 42, 43, 44, 3, 12, 4, 12, 99, 1, 0];
+        let (out, report) = optimize(&prog, &[]);
+        assert_eq!(1, report.jumps);
+        assert_eq!(3, report.blanked);
+        assert_eq!(vec![1105, 1, 6, 0, 0, 0], out[..6].to_vec());
+        assert_eq!(
+            cpu::run_with_inputs(&prog, &[7]).unwrap(),
+            cpu::run_with_inputs(&out, &[7]).unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_data() {
+        // reads a dead cell as data
+        let prog = vec![4, 5, 99, 1, 1, 77];
+        let (out, report) = optimize(&prog, &[]);
+        assert_eq!(vec![4, 5, 99, 0, 0, 77], out);
+        assert_eq!(2, report.blanked);
+
+        // overwrites an operand of the add it runs, which is not folded
+        let prog = vec![1101, 1, 1, 1, 99];
+        let (out, report) = optimize(&prog, &[]);
+        assert_eq!(prog, out);
+        assert_eq!(None, report.skipped);
+
+        // writes the halt it runs next, which could then jump anywhere
+        let prog = vec![1101, 1, 98, 4, 0];
+        let (out, report) = optimize(&prog, &[]);
+        assert_eq!(prog, out);
+        assert_eq!(
+            Some("read through the address at 4".to_string()),
+            report.skipped
+        );
+
+        // writes the address an output reads
+        let prog = vec![1101, 9, 0, 5, 4, 0, 99];
+        let report = optimize(&prog, &[]).1;
+        assert_eq!(
+            Some("read through the address at 5".to_string()),
+            report.skipped
+        );
+
+        // uses its first cell as a variable once it has run
+        let prog = vec![1101, 2, 3, 0, 1101, 4, 4, 11, 4, 11, 99, 0];
+        let (out, report) = optimize(&prog, &[]);
+        assert_eq!(None, report.skipped);
+        assert_eq!(1, report.folded);
+        assert_eq!(vec![1101, 2, 3, 0, 1101, 8, 0, 11], out[..8].to_vec());
+    }
+
+    #[test]
+    fn keeps_patched_cells() {
+        // out(mem[9] + mem[10]), with mem[9] set before the run
+        let prog = vec![1, 9, 10, 11, 4, 11, 99, 0, 0, 5, 6, 0];
+        assert_eq!(1, optimize(&prog, &[]).1.folded);
+        let (out, report) = optimize(&prog, &[9]);
+        assert_eq!(prog, out);
+        assert_eq!(0, report.folded);
+    }
+
+    #[test]
+    fn skips_relative_base() {
+        // the relative base can't be followed, the program is left alone
+        // with the reason, however much else could be folded
+        let progs = [
+            (
+                vec![109, 1, 99],
+                "relative access at 0 before the stack is set up",
+            ),
+            (
+                vec![1101, 2, 3, 9, 204, -1, 99, 42, 43, 44],
+                "relative access at 4 before the stack is set up",
+            ),
+            (
+                vec![1101, 2, 3, 9, 1105, 1, 9, 42, 43, 9, 1, 99],
+                "relative access at 9 before the stack is set up",
+            ),
+        ];
+        for (prog, reason) in progs.iter() {
+            let (out, report) = optimize(prog, &[]);
+            assert_eq!(prog, &out);
+            assert_eq!(Some(reason.to_string()), report.skipped);
+            assert_eq!((0, 0, 0), (report.folded, report.jumps, report.blanked));
+        }
+    }
+
+    #[test]
+    fn folds_around_the_stack() {
+        // the base is set past the image before the push
+        let prog = vec![109, 20, 1101, 2, 3, 13, 21101, 0, 7, 0, 4, 13, 99, 0];
+        let (out, report) = optimize(&prog, &[]);
+        assert_eq!(None, report.skipped);
+        assert_eq!(2, report.folded);
+        assert_eq!(vec![1101, 5, 0, 13, 21101, 7, 0, 0], out[2..10].to_vec());
+        assert_eq!(
+            cpu::run_with_inputs(&prog, &[]).unwrap(),
+            cpu::run_with_inputs(&out, &[]).unwrap()
+        );
+    }
+
+    // runs both programs on the same inputs, errors included
+    fn same_outputs(prog: &[i128], optimized: &[i128], inputs: &[i128]) {
+        let a: Vec<_> = cpu::outputs(prog, inputs.to_vec())
+            .map(|r| r.ok())
+            .collect();
+        let b: Vec<_> = cpu::outputs(optimized, inputs.to_vec())
+            .map(|r| r.ok())
+            .collect();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn shipped_inputs() {
+        let mut rewritten = vec![];
+        let days = [2, 5, 7, 9, 11, 13, 15, 17, 19, 21, 23];
+        for day in days.iter() {
+            let prog = cpu::parse_input(&format!("resources/day{}-input.txt", day)).unwrap();
+            // day 2 sets its noun and verb before the run
+            let patched = match day {
+                2 => vec![1, 2],
+                _ => vec![],
+            };
+            let (optimized, report) = optimize(&prog, &patched);
+            if optimized != prog {
+                rewritten.push(*day);
+            }
+
+            match day {
+                2 => {
+                    for &(noun, verb) in [(12, 2), (0, 0), (42, 99)].iter() {
+                        let run = |p: &[i128]| {
+                            let mut cpu = cpu::Cpu::new_detached(p);
+                            cpu.set_mem(1, noun);
+                            cpu.set_mem(2, verb);
+                            cpu.execute().unwrap();
+                            cpu.get_mem(0).unwrap()
+                        };
+                        assert_eq!(run(&prog), run(&optimized));
+                    }
+                }
+                5 => {
+                    for input in 0..10 {
+                        same_outputs(&prog, &optimized, &[input]);
+                    }
+                }
+                7 => {
+                    for phase in 0..10 {
+                        for signal in [0, 5, 1234].iter() {
+                            same_outputs(&prog, &optimized, &[phase, *signal, 7, 9, 11]);
+                        }
+                    }
+                }
+                19 => {
+                    for y in (0..60).step_by(3) {
+                        for x in (0..60).step_by(3) {
+                            same_outputs(&prog, &optimized, &[x, y]);
+                        }
+                    }
+                }
+                _ => assert_eq!(
+                    prog, optimized,
+                    "day {} changed without a check: {:?}",
+                    day, report
+                ),
+            }
+        }
+        assert!(!rewritten.is_empty());
+    }
+}
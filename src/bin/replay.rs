@@ -0,0 +1,31 @@
+use anyhow::Result;
+use intcode::cpu;
+
+// cargo run --bin replay -- resources/day13-input.txt day13.log 0=2
+// runs a program on the inputs of a recording and checks that it reads
+// and writes at the same instructions. `addr=value` patches the program
+// the way the recorded run did
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 3 {
+        anyhow::bail!("usage: replay <program> <recording> [addr=value ...]");
+    }
+
+    let mut prog = cpu::parse_input(&args[1])?;
+    for patch in args[3..].iter() {
+        let (addr, value) = match patch.split('=').collect::<Vec<_>>()[..] {
+            [addr, value] => (addr.parse::<usize>()?, value.parse()?),
+            _ => anyhow::bail!("bad patch '{}'", patch),
+        };
+        if addr >= prog.len() {
+            prog.resize(addr + 1, 0);
+        }
+        prog[addr] = value;
+    }
+
+    let recording = cpu::record::Recording::parse(&std::fs::read_to_string(&args[2])?)?;
+    let replayed = cpu::record::replay(&prog, &recording)?;
+    println!("{} events replayed", replayed.events.len());
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use cpu::header::{self, Protocol};
+use intcode::cpu;
+
+// the outputs up to the halt, or up to where the run stopped and why
+fn run(prog: &[i128], inputs: Vec<i128>) -> (Vec<i128>, Option<anyhow::Error>) {
+    let mut outputs = vec![];
+    for v in cpu::outputs(prog, inputs) {
+        match v {
+            Ok(v) => outputs.push(v),
+            Err(e) => return (outputs, Some(e)),
+        }
+    }
+    (outputs, None)
+}
+
+fn show(protocol: Protocol, outputs: &[i128]) -> String {
+    match protocol {
+        Protocol::Numeric => outputs.iter().map(|v| format!("{}\n", v)).collect(),
+        Protocol::Ascii => outputs
+            .iter()
+            .map(|v| match *v {
+                v if (0..128).contains(&v) => (v as u8 as char).to_string(),
+                v => format!("\n{}\n", v),
+            })
+            .collect(),
+        Protocol::Tuple(n) => outputs
+            .chunks(n)
+            .map(|chunk| {
+                let values: Vec<String> = chunk.iter().map(|v| v.to_string()).collect();
+                format!("{}\n", values.join(","))
+            })
+            .collect(),
+    }
+}
+
+// cargo run --bin run -- resources/day21-input.txt "NOT A J" "WALK"
+// runs any program the way its header says, guessing the protocol when it
+// doesn't. ascii programs take each argument as a line, the others take
+// numbers. with --check the samples of the header are run instead
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: run <program> [--check | inputs ...]");
+    }
+
+    let (header, mut prog) = header::load(&args[1])?;
+    header.apply(&mut prog);
+
+    if args.get(2).map(String::as_str) == Some("--check") {
+        for (n, sample) in header.samples.iter().enumerate() {
+            let (outputs, _) = run(&prog, sample.inputs.clone());
+            if outputs != sample.outputs {
+                anyhow::bail!(
+                    "sample {} gave {:?}, expected {:?}",
+                    n + 1,
+                    outputs,
+                    sample.outputs
+                );
+            }
+        }
+        println!("{} samples ok", header.samples.len());
+        return Ok(());
+    }
+
+    let numbers: Result<Vec<i128>, _> = args[2..].iter().map(|v| v.parse()).collect();
+    let protocol = match header.protocol {
+        Some(protocol) => protocol,
+        None => header::fingerprint(&prog, numbers.as_deref().unwrap_or(&[])),
+    };
+    let inputs = match protocol {
+        Protocol::Ascii => args[2..]
+            .iter()
+            .flat_map(|line| line.bytes().chain(Some(b'\n')))
+            .map(|b| b as i128)
+            .collect(),
+        _ => numbers?,
+    };
+
+    if let Some(name) = &header.name {
+        eprintln!("{}", name);
+    }
+    eprintln!("io: {}", protocol);
+    let (outputs, stop) = run(&prog, inputs);
+    print!("{}", show(protocol, &outputs));
+    if let Some(e) = stop {
+        eprintln!("stopped: {}", e);
+    }
+
+    Ok(())
+}
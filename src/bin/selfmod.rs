@@ -0,0 +1,41 @@
+use anyhow::Result;
+use cpu::Cpu;
+use intcode::cpu;
+
+// cargo run --bin selfmod -- resources/day5-input.txt 5
+// runs a program on the given inputs and lists every write to code that
+// already ran, to tell whether it can be cached or transpiled as it is
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        anyhow::bail!("usage: selfmod <program> [inputs ...]");
+    }
+
+    let prog = cpu::parse_input(&args[1])?;
+    let inputs: Vec<i128> = args[2..]
+        .iter()
+        .map(|v| v.parse())
+        .collect::<Result<_, _>>()?;
+    let mut cpu = Cpu::new_detached(&prog);
+    cpu.report_self_modification();
+    let mut outputs = cpu.into_outputs(inputs);
+    let stop = outputs.find_map(|v| v.err());
+    let cpu = outputs.into_cpu();
+
+    let log = cpu.self_modifications().unwrap();
+    for modification in log.iter() {
+        println!("{}", modification);
+    }
+    let cells: std::collections::BTreeSet<u128> = log.iter().map(|m| m.addr).collect();
+    println!(
+        "{} writes to {} cells of code that ran, in {} instructions",
+        log.len(),
+        cells.len(),
+        cpu.count()
+    );
+    if let Some(e) = stop {
+        println!("stopped: {}", e);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use crate::cpu::{Cpu, Image};
+use anyhow::Result;
+use rayon::prelude::*;
+
+// evaluates every point of the space on the rayon pool, results keep the
+// order of the space
+#[allow(dead_code)]
+pub fn all<S, T, F>(space: Vec<S>, eval: F) -> Vec<(S, Result<T>)>
+where
+    S: Send,
+    T: Send,
+    F: Fn(&S) -> Result<T> + Sync,
+{
+    space
+        .into_par_iter()
+        .map(|point| {
+            let result = eval(&point);
+            (point, result)
+        })
+        .collect()
+}
+
+// the point with the highest score, points that fail to evaluate are skipped
+#[allow(dead_code)]
+pub fn best<S, K, F>(space: Vec<S>, score: F) -> Option<(S, K)>
+where
+    S: Send,
+    K: Ord + Send,
+    F: Fn(&S) -> Result<K> + Sync,
+{
+    space
+        .into_par_iter()
+        .filter_map(|point| score(&point).ok().map(|k| (point, k)))
+        .max_by(|a, b| a.1.cmp(&b.1))
+}
+
+// the first point in the order of the space that matches
+#[allow(dead_code)]
+pub fn find<S, F>(space: Vec<S>, pred: F) -> Option<S>
+where
+    S: Send,
+    F: Fn(&S) -> Result<bool> + Sync,
+{
+    space
+        .into_par_iter()
+        .find_first(|point| pred(point).unwrap_or(false))
+}
+
+// memory patches applied before the run and the inputs fed to it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Case {
+    pub patches: Vec<(u128, i128)>,
+    pub inputs: Vec<i128>,
+}
+
+impl Case {
+    #[allow(dead_code)]
+    pub fn patched(patches: &[(u128, i128)]) -> Case {
+        Case {
+            patches: patches.to_vec(),
+            inputs: vec![],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_inputs(inputs: &[i128]) -> Case {
+        Case {
+            patches: vec![],
+            inputs: inputs.to_vec(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub struct Outcome {
+    pub outputs: Vec<i128>,
+    pub cpu: Cpu,
+}
+
+// runs cases of a single program, every run starts from the shared image
+#[allow(dead_code)]
+pub struct Sweep {
+    image: Image,
+}
+
+impl Sweep {
+    #[allow(dead_code)]
+    pub fn new(program: impl Into<Image>) -> Sweep {
+        Sweep {
+            image: program.into(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    #[allow(dead_code)]
+    pub fn run(&self, case: &Case) -> Result<Outcome> {
+        let mut cpu = Cpu::new_detached(&self.image);
+        for (k, v) in case.patches.iter() {
+            cpu.set_mem(*k, *v);
+        }
+
+        let mut outputs = cpu.into_outputs(case.inputs.iter().copied());
+        let values = outputs.by_ref().collect::<Result<Vec<i128>>>()?;
+
+        Ok(Outcome {
+            outputs: values,
+            cpu: outputs.into_cpu(),
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn all<T, F>(&self, cases: Vec<Case>, eval: F) -> Vec<(Case, Result<T>)>
+    where
+        T: Send,
+        F: Fn(&Outcome) -> Result<T> + Sync,
+    {
+        all(cases, |case| eval(&self.run(case)?))
+    }
+
+    #[allow(dead_code)]
+    pub fn best<K, F>(&self, cases: Vec<Case>, score: F) -> Option<(Case, K)>
+    where
+        K: Ord + Send,
+        F: Fn(&Outcome) -> Result<K> + Sync,
+    {
+        best(cases, |case| score(&self.run(case)?))
+    }
+
+    #[allow(dead_code)]
+    pub fn find<F>(&self, cases: Vec<Case>, pred: F) -> Option<Case>
+    where
+        F: Fn(&Outcome) -> Result<bool> + Sync,
+    {
+        find(cases, |case| pred(&self.run(case)?))
+    }
+}
+
+#[cfg(test)]
+mod sweep_tests {
+    use super::*;
+
+    // mem[0] = mem[mem[1]] + mem[mem[2]], then outputs input * mem[0]
+    const PROG: [i128; 14] = [1, 0, 0, 0, 3, 13, 2, 13, 0, 13, 4, 13, 99, 0];
+
+    #[test]
+    fn all_keeps_order() {
+        let sweep = Sweep::new(&PROG.to_vec());
+        let cases = (1..=4).map(|n| Case::with_inputs(&[n])).collect();
+
+        let results: Vec<i128> = sweep
+            .all(cases, |outcome| Ok(outcome.outputs[0]))
+            .into_iter()
+            .map(|(_, result)| result.unwrap())
+            .collect();
+        assert_eq!(vec![2, 4, 6, 8], results);
+    }
+
+    #[test]
+    fn failing_runs() {
+        let sweep = Sweep::new(&PROG.to_vec());
+        let cases = vec![Case::with_inputs(&[1]), Case::default()];
+
+        let results = sweep.all(cases, |outcome| Ok(outcome.outputs.len()));
+        assert_eq!(1, *results[0].1.as_ref().unwrap());
+        assert!(results[1].1.is_err());
+
+        let best = sweep.best(vec![Case::default(), Case::with_inputs(&[3])], |outcome| {
+            Ok(outcome.outputs[0])
+        });
+        assert_eq!(Some((Case::with_inputs(&[3]), 6)), best);
+    }
+
+    #[test]
+    fn find_patch() {
+        let sweep = Sweep::new(&PROG.to_vec());
+        let case = |a, b| Case {
+            patches: vec![(1, a), (2, b)],
+            inputs: vec![1],
+        };
+        let cases = (0..10)
+            .flat_map(|a| (0..10).map(move |b| case(a, b)))
+            .collect();
+
+        // only 13 + 13 adds up, the first pair pointing to 13s is 5, 5
+        let found = sweep.find(cases, |outcome| Ok(outcome.cpu.get_mem(0)? == 26));
+        assert_eq!(Some(case(5, 5)), found);
+    }
+
+    #[test]
+    fn best_of_space() {
+        let space: Vec<i128> = (-5..5).collect();
+        let best = best(space, |x| Ok(-(x - 2) * (x - 2)));
+        assert_eq!(Some((2, 0)), best);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub type Model = BTreeMap<String, i128>;
+
+// values of a symbolic run. constants are folded as the tree is built, so a
+// run without symbols only ever sees `Const`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Const(i128),
+    Var(String),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Lt(Box<Expr>, Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>),
+    // a read through an address that is not known
+    Load(Box<Expr>),
+}
+
+use Expr::*;
+
+impl Expr {
+    pub fn var(name: &str) -> Expr {
+        Var(name.to_string())
+    }
+
+    pub fn add(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Const(a), Const(b)) => Const(a + b),
+            (Const(0), e) | (e, Const(0)) => e,
+            (a, b) => Add(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn mul(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Const(a), Const(b)) => Const(a * b),
+            (Const(0), _) | (_, Const(0)) => Const(0),
+            (Const(1), e) | (e, Const(1)) => e,
+            (a, b) => Mul(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn sub(a: Expr, b: Expr) -> Expr {
+        Expr::add(a, Expr::mul(Const(-1), b))
+    }
+
+    pub fn lt(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Const(a), Const(b)) => Const((a < b) as i128),
+            (a, b) => Lt(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn eq(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Const(a), Const(b)) => Const((a == b) as i128),
+            (a, b) => Eq(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn load(addr: Expr) -> Expr {
+        Load(Box::new(addr))
+    }
+
+    pub fn as_const(&self) -> Option<i128> {
+        match self {
+            Const(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    // `None` when a variable is missing from the model or a load is involved
+    pub fn eval(&self, model: &Model) -> Option<i128> {
+        match self {
+            Const(v) => Some(*v),
+            Var(name) => model.get(name).copied(),
+            Add(a, b) => Some(a.eval(model)? + b.eval(model)?),
+            Mul(a, b) => Some(a.eval(model)? * b.eval(model)?),
+            Lt(a, b) => Some((a.eval(model)? < b.eval(model)?) as i128),
+            Eq(a, b) => Some((a.eval(model)? == b.eval(model)?) as i128),
+            Load(_) => None,
+        }
+    }
+
+    pub fn vars(&self, out: &mut Vec<String>) {
+        match self {
+            Const(_) => {}
+            Var(name) => {
+                if !out.contains(name) {
+                    out.push(name.clone());
+                }
+            }
+            Add(a, b) | Mul(a, b) | Lt(a, b) | Eq(a, b) => {
+                a.vars(out);
+                b.vars(out);
+            }
+            Load(a) => a.vars(out),
+        }
+    }
+
+    // sum of coefficient * variable plus a constant, if the tree is linear
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Const(v) => Some(Linear {
+                terms: BTreeMap::new(),
+                constant: *v,
+            }),
+            Var(name) => {
+                let mut terms = BTreeMap::new();
+                terms.insert(name.clone(), 1);
+                Some(Linear { terms, constant: 0 })
+            }
+            Add(a, b) => {
+                let mut sum = a.linear()?;
+                let b = b.linear()?;
+                for (name, coeff) in b.terms {
+                    *sum.terms.entry(name).or_insert(0) += coeff;
+                }
+                sum.terms.retain(|_, coeff| *coeff != 0);
+                sum.constant += b.constant;
+                Some(sum)
+            }
+            Mul(a, b) => match (a.as_const(), b.as_const()) {
+                (Some(k), _) => Some(b.linear()?.scale(k)),
+                (_, Some(k)) => Some(a.linear()?.scale(k)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Const(v) => write!(f, "{}", v),
+            Var(name) => write!(f, "{}", name),
+            Add(a, b) => write!(f, "({} + {})", a, b),
+            Mul(a, b) => write!(f, "({} * {})", a, b),
+            Lt(a, b) => write!(f, "({} < {})", a, b),
+            Eq(a, b) => write!(f, "({} == {})", a, b),
+            Load(a) => write!(f, "mem[{}]", a),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linear {
+    pub terms: BTreeMap<String, i128>,
+    pub constant: i128,
+}
+
+impl Linear {
+    fn scale(mut self, k: i128) -> Linear {
+        self.terms.values_mut().for_each(|coeff| *coeff *= k);
+        self.terms.retain(|_, coeff| *coeff != 0);
+        self.constant *= k;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rel {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+}
+
+// `lhs rel 0`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub lhs: Expr,
+    pub rel: Rel,
+}
+
+impl Constraint {
+    pub fn new(lhs: Expr, rel: Rel) -> Constraint {
+        Constraint { lhs, rel }
+    }
+
+    // the condition of a jump holds (`nonzero`) or not
+    pub fn branch(cond: &Expr, nonzero: bool) -> Constraint {
+        match (cond, nonzero) {
+            (Lt(a, b), true) => Constraint::new(Expr::sub(*a.clone(), *b.clone()), Rel::Lt),
+            (Lt(a, b), false) => Constraint::new(Expr::sub(*a.clone(), *b.clone()), Rel::Ge),
+            (Eq(a, b), true) => Constraint::new(Expr::sub(*a.clone(), *b.clone()), Rel::Eq),
+            (Eq(a, b), false) => Constraint::new(Expr::sub(*a.clone(), *b.clone()), Rel::Ne),
+            (cond, true) => Constraint::new(cond.clone(), Rel::Ne),
+            (cond, false) => Constraint::new(cond.clone(), Rel::Eq),
+        }
+    }
+
+    pub fn holds(&self, v: i128) -> bool {
+        match self.rel {
+            Rel::Eq => v == 0,
+            Rel::Ne => v != 0,
+            Rel::Lt => v < 0,
+            Rel::Ge => v >= 0,
+        }
+    }
+
+    pub fn eval(&self, model: &Model) -> Option<bool> {
+        self.lhs.eval(model).map(|v| self.holds(v))
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self.rel {
+            Rel::Eq => "==",
+            Rel::Ne => "!=",
+            Rel::Lt => "<",
+            Rel::Ge => ">=",
+        };
+        write!(f, "{} {} 0", self.lhs, op)
+    }
+}
+
+#[cfg(test)]
+mod expr_tests {
+    use super::*;
+
+    #[test]
+    fn folding() {
+        let x = Expr::var("x");
+        assert_eq!(Const(7), Expr::add(Const(3), Const(4)));
+        assert_eq!(x, Expr::add(Const(0), x.clone()));
+        assert_eq!(x, Expr::mul(x.clone(), Const(1)));
+        assert_eq!(Const(0), Expr::mul(x.clone(), Const(0)));
+        assert_eq!(Const(1), Expr::lt(Const(1), Const(2)));
+        assert_eq!(
+            "((x * 3) + 5)",
+            Expr::add(Expr::mul(x, Const(3)), Const(5)).to_string()
+        );
+    }
+
+    #[test]
+    fn linear_forms() {
+        let (x, y) = (Expr::var("x"), Expr::var("y"));
+        // 2 * (x + 3) + y - x
+        let e = Expr::sub(
+            Expr::add(
+                Expr::mul(Const(2), Expr::add(x.clone(), Const(3))),
+                y.clone(),
+            ),
+            x.clone(),
+        );
+        let linear = e.linear().unwrap();
+        assert_eq!(6, linear.constant);
+        assert_eq!(Some(&1), linear.terms.get("x"));
+        assert_eq!(Some(&1), linear.terms.get("y"));
+
+        assert_eq!(None, Expr::mul(x.clone(), y.clone()).linear());
+        assert_eq!(None, Expr::load(x).linear());
+    }
+
+    #[test]
+    fn branches() {
+        let lt = Expr::lt(Expr::var("x"), Const(10));
+        let mut model = Model::new();
+        model.insert("x".to_string(), 4);
+
+        assert_eq!(Some(true), Constraint::branch(&lt, true).eval(&model));
+        assert_eq!(Some(false), Constraint::branch(&lt, false).eval(&model));
+        assert_eq!("(x + -10) < 0", Constraint::branch(&lt, true).to_string());
+        assert_eq!(None, Constraint::branch(&Expr::var("y"), true).eval(&model));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::cpu::Image;
+use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
+
+mod expr;
+mod solver;
+pub use expr::{Constraint, Expr, Model, Rel};
+pub use solver::Solver;
+
+// how a path of a symbolic run ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum End {
+    Halt,
+    Reached(u128),
+    StepLimit,
+    // symbolic jump targets, write addresses and relative bases
+    Unsupported(String),
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub outputs: Vec<Expr>,
+    pub conditions: Vec<Constraint>,
+    pub end: End,
+    state: State,
+}
+
+impl Path {
+    pub fn mem(&self, k: u128) -> Expr {
+        self.state.get(k)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    image: Image,
+    pc: u128,
+    base: i128,
+    steps: usize,
+    mem: HashMap<u128, Expr>,
+    inputs: VecDeque<Expr>,
+    reads: usize,
+    outputs: Vec<Expr>,
+    conditions: Vec<Constraint>,
+}
+
+impl State {
+    fn get(&self, k: u128) -> Expr {
+        match self.mem.get(&k) {
+            Some(e) => e.clone(),
+            None => Expr::Const(self.image.get(k)),
+        }
+    }
+
+    fn param(&self, n: u128, modes: i128) -> Expr {
+        let raw = self.get(self.pc + n);
+        let addr = match (modes / 10i128.pow(n as u32 - 1) % 10, raw) {
+            (1, raw) => return raw,
+            (2, Expr::Const(offset)) => self.base + offset,
+            (_, Expr::Const(addr)) => addr,
+            (2, raw) => return Expr::load(Expr::add(Expr::Const(self.base), raw)),
+            (_, raw) => return Expr::load(raw),
+        };
+        self.get(addr as u128)
+    }
+
+    fn addr(&self, n: u128, modes: i128) -> Result<u128, End> {
+        let mode = modes / 10i128.pow(n as u32 - 1) % 10;
+        match self.get(self.pc + n) {
+            Expr::Const(addr) if mode == 2 => Ok((self.base + addr) as u128),
+            Expr::Const(addr) => Ok(addr as u128),
+            e => Err(End::Unsupported(format!("write to {} at {}", e, self.pc))),
+        }
+    }
+}
+
+// a conditional jump on a symbolic value
+struct Fork {
+    cond: Expr,
+    taken_if_nonzero: bool,
+    target: u128,
+    next: u128,
+}
+
+enum Stop {
+    End(End),
+    Fork(Fork),
+}
+
+// Runs a program with some memory cells and inputs replaced by variables.
+// Every jump on a symbolic condition forks the run, each path keeps the
+// conditions it took. Inputs past the given ones are fresh variables
+// `in0`, `in1`, ...
+pub struct Engine {
+    image: Image,
+    symbols: Vec<(u128, String)>,
+    inputs: Vec<Expr>,
+    max_steps: usize,
+    max_paths: usize,
+}
+
+impl Engine {
+    pub fn new(program: impl Into<Image>) -> Engine {
+        Engine {
+            image: program.into(),
+            symbols: vec![],
+            inputs: vec![],
+            max_steps: 100_000,
+            max_paths: 1000,
+        }
+    }
+
+    pub fn symbol(mut self, addr: u128, name: &str) -> Engine {
+        self.symbols.push((addr, name.to_string()));
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn inputs(mut self, inputs: Vec<Expr>) -> Engine {
+        self.inputs = inputs;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn limits(mut self, max_steps: usize, max_paths: usize) -> Engine {
+        self.max_steps = max_steps;
+        self.max_paths = max_paths;
+        self
+    }
+
+    // every path that the solver can't rule out
+    pub fn explore(&self, solver: &Solver) -> Result<Vec<Path>> {
+        self.search(solver, None)
+    }
+
+    // values for the variables that take the program to `pc`
+    #[allow(dead_code)]
+    pub fn reach(&self, pc: u128, solver: &Solver) -> Result<Option<(Path, Model)>> {
+        for path in self.search(solver, Some(pc))? {
+            if path.end != End::Reached(pc) {
+                continue;
+            }
+            if let Some(model) = solver.solve(&path.conditions) {
+                return Ok(Some((path, model)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn search(&self, solver: &Solver, target: Option<u128>) -> Result<Vec<Path>> {
+        let mut state = State {
+            image: self.image.clone(),
+            pc: 0,
+            base: 0,
+            steps: 0,
+            mem: HashMap::new(),
+            inputs: self.inputs.iter().cloned().collect(),
+            reads: 0,
+            outputs: vec![],
+            conditions: vec![],
+        };
+        for (addr, name) in self.symbols.iter() {
+            state.mem.insert(*addr, Expr::var(name));
+        }
+
+        let mut paths = vec![];
+        let mut queue = VecDeque::new();
+        queue.push_back(state);
+
+        while let Some(mut state) = queue.pop_front() {
+            let fork = match self.run(&mut state, target)? {
+                Stop::End(end) => {
+                    paths.push(Path {
+                        outputs: state.outputs.clone(),
+                        conditions: state.conditions.clone(),
+                        end,
+                        state,
+                    });
+                    continue;
+                }
+                Stop::Fork(fork) => fork,
+            };
+
+            for &nonzero in [true, false].iter() {
+                let mut branch = state.clone();
+                branch
+                    .conditions
+                    .push(Constraint::branch(&fork.cond, nonzero));
+                branch.pc = if nonzero == fork.taken_if_nonzero {
+                    fork.target
+                } else {
+                    fork.next
+                };
+
+                if solver.solve(&branch.conditions).is_some() {
+                    queue.push_back(branch);
+                }
+            }
+
+            if paths.len() + queue.len() > self.max_paths {
+                anyhow::bail!("more than {} paths", self.max_paths);
+            }
+        }
+
+        Ok(paths)
+    }
+
+    fn run(&self, state: &mut State, target: Option<u128>) -> Result<Stop> {
+        loop {
+            if target == Some(state.pc) {
+                return Ok(Stop::End(End::Reached(state.pc)));
+            }
+            if state.steps == self.max_steps {
+                return Ok(Stop::End(End::StepLimit));
+            }
+            state.steps += 1;
+
+            let inst = match state.get(state.pc) {
+                Expr::Const(inst) => inst,
+                e => {
+                    let msg = format!("instruction {} at {}", e, state.pc);
+                    return Ok(Stop::End(End::Unsupported(msg)));
+                }
+            };
+            let (opcode, modes) = (inst % 100, inst / 100);
+
+            macro_rules! store {
+                ($n:expr, $value:expr) => {
+                    match state.addr($n, modes) {
+                        Ok(addr) => {
+                            let value = $value;
+                            state.mem.insert(addr, value);
+                        }
+                        Err(end) => return Ok(Stop::End(end)),
+                    }
+                };
+            }
+
+            match opcode {
+                1 | 2 | 7 | 8 => {
+                    let (a, b) = (state.param(1, modes), state.param(2, modes));
+                    store!(
+                        3,
+                        match opcode {
+                            1 => Expr::add(a, b),
+                            2 => Expr::mul(a, b),
+                            7 => Expr::lt(a, b),
+                            _ => Expr::eq(a, b),
+                        }
+                    );
+                    state.pc += 4;
+                }
+                3 => {
+                    let reads = state.reads;
+                    store!(
+                        1,
+                        state
+                            .inputs
+                            .pop_front()
+                            .unwrap_or_else(|| Expr::var(&format!("in{}", reads)))
+                    );
+                    state.reads += 1;
+                    state.pc += 2;
+                }
+                4 => {
+                    let value = state.param(1, modes);
+                    state.outputs.push(value);
+                    state.pc += 2;
+                }
+                5 | 6 => {
+                    let cond = state.param(1, modes);
+                    let target = match state.param(2, modes) {
+                        Expr::Const(target) => target as u128,
+                        e => {
+                            let msg = format!("jump to {} at {}", e, state.pc);
+                            return Ok(Stop::End(End::Unsupported(msg)));
+                        }
+                    };
+                    let taken_if_nonzero = opcode == 5;
+
+                    match cond {
+                        Expr::Const(c) if (c != 0) == taken_if_nonzero => state.pc = target,
+                        Expr::Const(_) => state.pc += 3,
+                        cond => {
+                            return Ok(Stop::Fork(Fork {
+                                cond,
+                                taken_if_nonzero,
+                                target,
+                                next: state.pc + 3,
+                            }))
+                        }
+                    }
+                }
+                9 => match state.param(1, modes) {
+                    Expr::Const(offset) => {
+                        state.base += offset;
+                        state.pc += 2;
+                    }
+                    e => {
+                        let msg = format!("relative base {} at {}", e, state.pc);
+                        return Ok(Stop::End(End::Unsupported(msg)));
+                    }
+                },
+                99 => return Ok(Stop::End(End::Halt)),
+                _ => anyhow::bail!("unknown opcode '{}' at {}", opcode, state.pc),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod symbolic_tests {
+    use super::*;
+
+    #[test]
+    fn expression_outputs() {
+        // outputs in0 * 3 + 5
+        let prog = vec![3, 13, 1002, 13, 3, 13, 1001, 13, 5, 13, 4, 13, 99, 0];
+        let paths = Engine::new(&prog).explore(&Solver::new(0, 10)).unwrap();
+
+        assert_eq!(1, paths.len());
+        assert_eq!(End::Halt, paths[0].end);
+        assert_eq!("((in0 * 3) + 5)", paths[0].outputs[0].to_string());
+    }
+
+    #[test]
+    fn forks_on_jumps() {
+        // in0 < 10 ? output 1 : output 2
+        let prog = vec![
+            3, 20, 1007, 20, 10, 21, 1005, 21, 13, 104, 2, 99, 0, 104, 1, 99,
+        ];
+        let paths = Engine::new(&prog).explore(&Solver::new(0, 100)).unwrap();
+
+        assert_eq!(2, paths.len());
+        assert_eq!(vec![Expr::Const(1)], paths[0].outputs);
+        assert_eq!("(in0 + -10) < 0", paths[0].conditions[0].to_string());
+        assert_eq!(vec![Expr::Const(2)], paths[1].outputs);
+
+        // the solver prunes the branch that can't be taken
+        let paths = Engine::new(&prog).explore(&Solver::new(0, 5)).unwrap();
+        assert_eq!(1, paths.len());
+    }
+
+    #[test]
+    fn reach_pc() {
+        // 0: in0 -> 40, 2: in1 -> 41, 4: 40 == 7 -> 42, 8: jz 42 -> 22
+        // 11: 41 < 40 -> 43, 15: jz 43 -> 22, 18: out 1, 20: halt, 22: halt
+        let prog = vec![
+            3, 40, 3, 41, 1008, 40, 7, 42, 1006, 42, 22, 7, 41, 40, 43, 1006, 43, 22, 104, 1, 99,
+            0, 99,
+        ];
+        let solver = Solver::new(0, 20);
+        let (path, model) = Engine::new(&prog).reach(18, &solver).unwrap().unwrap();
+        assert_eq!(7, model["in0"]);
+        assert!(model["in1"] < 7);
+        assert_eq!(2, path.conditions.len());
+
+        assert!(Engine::new(&prog)
+            .reach(18, &solver.domain("in0", 0, 6))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn unsupported_writes() {
+        let prog = vec![3, 9, 1, 0, 0, 0, 99];
+        let paths = Engine::new(&prog)
+            .symbol(5, "x")
+            .explore(&Solver::new(0, 1))
+            .unwrap();
+        match &paths[0].end {
+            End::Unsupported(msg) => assert!(msg.starts_with("write to x")),
+            end => panic!("unexpected end {:?}", end),
+        }
+    }
+}
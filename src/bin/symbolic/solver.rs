@@ -0,0 +1,193 @@
+use super::expr::{Constraint, Linear, Model, Rel};
+use std::collections::BTreeMap;
+
+// Finds integer values for the variables of a set of constraints, each
+// variable ranging over a closed domain. Variables are assigned one at a
+// time and the linear constraints prune a partial assignment as soon as
+// the remaining domains can no longer satisfy them. Constraints that are
+// not linear are only checked once every variable has a value, the ones
+// reading unknown memory are assumed to hold.
+pub struct Solver {
+    default: (i128, i128),
+    domains: BTreeMap<String, (i128, i128)>,
+}
+
+impl Solver {
+    pub fn new(lo: i128, hi: i128) -> Solver {
+        Solver {
+            default: (lo, hi),
+            domains: BTreeMap::new(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn domain(mut self, name: &str, lo: i128, hi: i128) -> Solver {
+        self.domains.insert(name.to_string(), (lo, hi));
+        self
+    }
+
+    fn domain_of(&self, name: &str) -> (i128, i128) {
+        *self.domains.get(name).unwrap_or(&self.default)
+    }
+
+    pub fn solve(&self, constraints: &[Constraint]) -> Option<Model> {
+        let mut vars = vec![];
+        for constraint in constraints {
+            constraint.lhs.vars(&mut vars);
+        }
+
+        let linear: Vec<(Linear, Rel)> = constraints
+            .iter()
+            .filter_map(|c| c.lhs.linear().map(|l| (l, c.rel)))
+            .collect();
+
+        let mut model = Model::new();
+        if self.assign(&vars, &linear, constraints, &mut model) {
+            Some(model)
+        } else {
+            None
+        }
+    }
+
+    fn assign(
+        &self,
+        vars: &[String],
+        linear: &[(Linear, Rel)],
+        constraints: &[Constraint],
+        model: &mut Model,
+    ) -> bool {
+        if !linear.iter().all(|(l, rel)| self.feasible(l, *rel, model)) {
+            return false;
+        }
+
+        let (name, rest) = match vars.split_first() {
+            Some(split) => split,
+            None => return constraints.iter().all(|c| c.eval(model) != Some(false)),
+        };
+
+        let (lo, hi) = match self.forced(name, linear, model) {
+            Some(Some(v)) => (v, v),
+            Some(None) => return false,
+            None => self.domain_of(name),
+        };
+
+        for v in lo..=hi {
+            model.insert(name.clone(), v);
+            if self.assign(rest, linear, constraints, model) {
+                return true;
+            }
+        }
+        model.remove(name);
+        false
+    }
+
+    // the bounds of `l` over every value the unassigned variables can take
+    fn bounds(&self, l: &Linear, model: &Model) -> (i128, i128) {
+        l.terms.iter().fold(
+            (l.constant, l.constant),
+            |(min, max), (name, coeff)| match model.get(name) {
+                Some(v) => (min + coeff * v, max + coeff * v),
+                None => {
+                    let (lo, hi) = self.domain_of(name);
+                    let (a, b) = (coeff * lo, coeff * hi);
+                    (min + a.min(b), max + a.max(b))
+                }
+            },
+        )
+    }
+
+    fn feasible(&self, l: &Linear, rel: Rel, model: &Model) -> bool {
+        let (min, max) = self.bounds(l, model);
+        match rel {
+            Rel::Eq => min <= 0 && 0 <= max,
+            Rel::Ne => !(min == 0 && max == 0),
+            Rel::Lt => min < 0,
+            Rel::Ge => max >= 0,
+        }
+    }
+
+    // an equality where `name` is the last unknown pins it down, the inner
+    // `None` means no value of the domain fits
+    fn forced(&self, name: &str, linear: &[(Linear, Rel)], model: &Model) -> Option<Option<i128>> {
+        linear.iter().find_map(|(l, rel)| {
+            let coeff = *l.terms.get(name)?;
+            let others = l
+                .terms
+                .keys()
+                .filter(|other| *other != name)
+                .all(|other| model.contains_key(other));
+            if *rel != Rel::Eq || !others {
+                return None;
+            }
+
+            let rest: i128 = l.constant
+                + l.terms
+                    .iter()
+                    .filter(|(other, _)| *other != name)
+                    .map(|(other, c)| c * model[other])
+                    .sum::<i128>();
+            let (lo, hi) = self.domain_of(name);
+            let v = -rest / coeff;
+            Some(if rest % coeff == 0 && lo <= v && v <= hi {
+                Some(v)
+            } else {
+                None
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod solver_tests {
+    use super::super::expr::Expr;
+    use super::*;
+
+    fn x() -> Expr {
+        Expr::var("x")
+    }
+
+    fn y() -> Expr {
+        Expr::var("y")
+    }
+
+    #[test]
+    fn linear_equation() {
+        // 300000 * x + y + 5 == 2100042
+        let lhs = Expr::add(
+            Expr::add(Expr::mul(Expr::Const(300000), x()), y()),
+            Expr::Const(5 - 2100042),
+        );
+        let model = Solver::new(0, 99)
+            .solve(&[Constraint::new(lhs, Rel::Eq)])
+            .unwrap();
+        assert_eq!(7, model["x"]);
+        assert_eq!(37, model["y"]);
+    }
+
+    #[test]
+    fn inequalities() {
+        let solver = Solver::new(-10, 10).domain("y", 3, 3);
+        // x < y, x >= 2, x != 2
+        let constraints = vec![
+            Constraint::new(Expr::sub(x(), y()), Rel::Lt),
+            Constraint::new(Expr::sub(x(), Expr::Const(2)), Rel::Ge),
+            Constraint::new(Expr::sub(x(), Expr::Const(2)), Rel::Ne),
+        ];
+        assert_eq!(None, solver.solve(&constraints));
+        assert_eq!(Some(2), solver.solve(&constraints[..2]).map(|m| m["x"]));
+    }
+
+    #[test]
+    fn non_linear() {
+        // x * y == 12, x + y == 7
+        let constraints = vec![
+            Constraint::new(Expr::sub(Expr::mul(x(), y()), Expr::Const(12)), Rel::Eq),
+            Constraint::new(Expr::sub(Expr::add(x(), y()), Expr::Const(7)), Rel::Eq),
+        ];
+        let model = Solver::new(0, 10).solve(&constraints).unwrap();
+        assert_eq!((3, 4), (model["x"], model["y"]));
+
+        let load = Constraint::new(Expr::load(x()), Rel::Eq);
+        assert!(Solver::new(0, 3).solve(&[load]).is_some());
+    }
+}
@@ -0,0 +1,200 @@
+use super::Memory;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+// how many of the last writes can hold a return address
+const RECENT: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    // where the function starts
+    pub entry: u128,
+    // the jump that called it
+    pub site: u128,
+    // where it returns to and the cell that holds it
+    pub ret: u128,
+    pub slot: u128,
+    // the relative base at the call
+    pub base: i128,
+}
+
+// Calls and returns seen in a run. A taken jump is a call when one of the
+// cells written just before holds the address after the jump, which is
+// how code stores its return address before jumping. A jump to the return
+// address of a frame on the stack returns from it and every frame above.
+// The program start is function 0.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallGraph {
+    recent: VecDeque<u128>,
+    pub stack: Vec<Frame>,
+    // calls from one function to another
+    pub edges: BTreeMap<u128, BTreeMap<u128, usize>>,
+    pub max_depth: usize,
+}
+
+impl CallGraph {
+    pub fn write(&mut self, addr: u128) {
+        self.recent.retain(|k| *k != addr);
+        self.recent.push_back(addr);
+        if self.recent.len() > RECENT {
+            self.recent.pop_front();
+        }
+    }
+
+    // a taken jump of the instruction at `pc`, jumps are 3 cells long
+    pub fn jump(&mut self, mem: &Memory, pc: u128, target: u128, base: i128) {
+        if let Some(n) = self.stack.iter().rposition(|f| f.ret == target) {
+            self.stack.truncate(n);
+            return;
+        }
+
+        let ret = pc + 3;
+        let slot = match self
+            .recent
+            .iter()
+            .rev()
+            .find(|k| mem.get(**k) == ret as i128)
+        {
+            Some(slot) => *slot,
+            None => return,
+        };
+        let caller = self.stack.last().map_or(0, |f| f.entry);
+        *self
+            .edges
+            .entry(caller)
+            .or_default()
+            .entry(target)
+            .or_insert(0) += 1;
+        self.stack.push(Frame {
+            entry: target,
+            site: pc,
+            ret,
+            slot,
+            base,
+        });
+        self.max_depth = self.max_depth.max(self.stack.len());
+        self.recent.clear();
+    }
+
+    // every function that was called and the program start
+    #[allow(dead_code)]
+    pub fn functions(&self) -> BTreeSet<u128> {
+        let mut functions: BTreeSet<u128> = self.edges.keys().copied().collect();
+        functions.extend(self.edges.values().flat_map(|calls| calls.keys()));
+        functions.insert(0);
+        functions
+    }
+
+    // the functions being run with the innermost first, each with where it
+    // is at: `pc` for the innermost, the call site for the others
+    #[allow(dead_code)]
+    pub fn backtrace(&self, pc: u128) -> String {
+        let mut at = pc;
+        let mut out = String::new();
+        for (n, frame) in self.stack.iter().rev().enumerate() {
+            out += &format!(
+                "#{:<3} fn {} at {}, returns to {} from [{}], rb {}\n",
+                n, frame.entry, at, frame.ret, frame.slot, frame.base
+            );
+            at = frame.site;
+        }
+        out += &format!("#{:<3} fn 0 at {}\n", self.stack.len(), at);
+        out
+    }
+
+    // the calls from the program start down, with how often each was made.
+    // recursion is shown once and not followed
+    #[allow(dead_code)]
+    pub fn tree(&self) -> String {
+        let mut out = "fn 0\n".to_string();
+        self.subtree(0, &mut vec![0], &mut out);
+        out
+    }
+
+    fn subtree(&self, entry: u128, path: &mut Vec<u128>, out: &mut String) {
+        let calls = match self.edges.get(&entry) {
+            Some(calls) => calls,
+            None => return,
+        };
+        for (callee, count) in calls.iter() {
+            let indent = "  ".repeat(path.len());
+            if path.contains(callee) {
+                *out += &format!("{}fn {} x{}, recursive\n", indent, callee, count);
+                continue;
+            }
+            *out += &format!("{}fn {} x{}\n", indent, callee, count);
+            path.push(*callee);
+            self.subtree(*callee, path, out);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod calls_tests {
+    use crate::cpu::{Cpu, Step};
+
+    // reads n and outputs fib(n) with a recursive fib at 20. callers store
+    // the return address at [rb+0], calls within fib move rb by 10
+    fn fib() -> Vec<i128> {
+        let mut prog = vec![
+            // main: rb = 100, [rb+1] = input, call fib, output [rb+2]
+            109, 100, 203, 1, 21101, 11, 0, 0, 1105, 1, 20, 204, 2, 99, 0, 0, 0, 0, 0, 0,
+            // 20: fib(n at [rb+1]) -> [rb+2], if n < 2 return n
+            21207, 1, 2, 3, 1206, 3, 34, 22101, 0, 1, 2, 2106, 0, 0,
+            // 34: [rb+4] = fib(n - 1)
+            21201, 1, -1, 11, 21101, 47, 0, 10, 109, 10, 1105, 1, 20, 109, -10, 22101, 0, 12, 4,
+            // 53: [rb+2] = [rb+4] + fib(n - 2), return
+            21201, 1, -2, 11, 21101, 66, 0, 10, 109, 10, 1105, 1, 20, 109, -10, 22201, 4, 12, 2,
+            2106, 0, 0,
+        ];
+        prog.resize(80, 0);
+        prog
+    }
+
+    fn run(input: i128) -> Cpu {
+        let mut cpu = Cpu::new_detached(&fib());
+        cpu.track_calls();
+        cpu.push_input(input);
+        loop {
+            match cpu.step().unwrap() {
+                Step::Output(v) => assert_eq!(vec![0, 1, 1, 2, 3, 5, 8][input as usize], v),
+                Step::Halt => break,
+                _ => {}
+            }
+        }
+        cpu
+    }
+
+    #[test]
+    fn recursion() {
+        let cpu = run(6);
+        let calls = cpu.calls().unwrap();
+        assert!(calls.stack.is_empty());
+        assert_eq!(6, calls.max_depth);
+        assert_eq!("fn 0\n  fn 20 x1\n    fn 20 x24, recursive\n", calls.tree());
+        assert_eq!(
+            vec![0, 20],
+            calls.functions().into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn backtrace() {
+        let mut cpu = Cpu::new_detached(&fib());
+        cpu.track_calls();
+        cpu.push_input(3);
+        // stops on the first n < 2
+        while cpu.pc() != 31 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(
+            concat!(
+                "#0   fn 20 at 31, returns to 47 from [120], rb 120\n",
+                "#1   fn 20 at 44, returns to 47 from [110], rb 110\n",
+                "#2   fn 20 at 44, returns to 11 from [100], rb 100\n",
+                "#3   fn 0 at 8\n",
+            ),
+            cpu.calls().unwrap().backtrace(cpu.pc())
+        );
+    }
+}
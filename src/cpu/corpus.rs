@@ -0,0 +1,198 @@
+// Test tables for cpus, see resources/intcode-corpus.txt for the format.
+// Not behind `cfg(test)`, the tests of `cpu_async` in the binaries run the
+// same corpus from here.
+use anyhow::Result;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Run {
+    pub inputs: Vec<i128>,
+    pub outputs: Vec<i128>,
+    // the start of memory after the run
+    pub memory: Option<Vec<i128>>,
+    // the message the run ends with instead of halting
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub name: String,
+    pub program: Vec<i128>,
+    pub runs: Vec<Run>,
+}
+
+// how a run went on some cpu
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outcome {
+    pub outputs: Vec<i128>,
+    // at least as much of the start of memory as the run checks
+    pub memory: Vec<i128>,
+    pub error: Option<String>,
+}
+
+fn numbers(s: &str) -> Result<Vec<i128>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| Ok(v.parse()?))
+        .collect()
+}
+
+pub fn parse(text: &str) -> Result<Vec<Case>> {
+    let mut cases: Vec<Case> = vec![];
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => anyhow::bail!("no key on line {}", n + 1),
+        };
+        if key == "name" {
+            cases.push(Case {
+                name: value.to_string(),
+                program: vec![],
+                runs: vec![],
+            });
+            continue;
+        }
+
+        let case = match cases.last_mut() {
+            Some(case) => case,
+            None => anyhow::bail!("no name before line {}", n + 1),
+        };
+        match key {
+            "program" => case.program = numbers(value)?,
+            "run" => {
+                let (inputs, outputs) = match value.split_once("->") {
+                    Some((i, o)) => (numbers(i)?, numbers(o)?),
+                    None => anyhow::bail!("bad run '{}' on line {}", value, n + 1),
+                };
+                case.runs.push(Run {
+                    inputs,
+                    outputs,
+                    ..Run::default()
+                });
+            }
+            "memory" | "error" => {
+                if case.runs.is_empty() {
+                    case.runs.push(Run::default());
+                }
+                let run = case.runs.last_mut().unwrap();
+                match key {
+                    "memory" => run.memory = Some(numbers(value)?),
+                    _ => run.error = Some(value.to_string()),
+                }
+            }
+            _ => anyhow::bail!("unknown key '{}' on line {}", key, n + 1),
+        }
+    }
+
+    for case in cases.iter() {
+        if case.program.is_empty() || case.runs.is_empty() {
+            anyhow::bail!("case '{}' needs a program and a run", case.name);
+        }
+    }
+    Ok(cases)
+}
+
+pub fn load(fname: &str) -> Result<Vec<Case>> {
+    parse(&std::fs::read_to_string(fname)?)
+}
+
+impl Run {
+    // what differs between the run and how it went
+    pub fn check(&self, outcome: &Outcome) -> Result<()> {
+        if outcome.outputs != self.outputs {
+            anyhow::bail!("outputs {:?}, expected {:?}", outcome.outputs, self.outputs);
+        }
+        if outcome.error != self.error {
+            anyhow::bail!("error {:?}, expected {:?}", outcome.error, self.error);
+        }
+        if let Some(memory) = &self.memory {
+            let got: Vec<i128> = (0..memory.len())
+                .map(|k| outcome.memory.get(k).copied().unwrap_or(0))
+                .collect();
+            if &got != memory {
+                anyhow::bail!("memory {:?}, expected {:?}", got, memory);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Every run of every case on a cpu, with the failures as "name, run n:
+// problem" lines. `run` gets the program and the run to make of it.
+pub fn failures<F>(cases: &[Case], mut run: F) -> Vec<String>
+where
+    F: FnMut(&[i128], &Run) -> Outcome,
+{
+    let mut failures = vec![];
+    for case in cases.iter() {
+        for (n, r) in case.runs.iter().enumerate() {
+            if let Err(e) = r.check(&run(&case.program, r)) {
+                failures.push(format!("{}, run {}: {}", case.name, n + 1, e));
+            }
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod corpus_tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        let cases = parse(concat!(
+            "# comment\n",
+            "name: echo\n",
+            "program: 3,0,4,0,99\n",
+            "run: 5 -> 5\n",
+            "memory: 5,0,4,0,99\n",
+            "run: -> 1,2\n",
+            "error: out\n",
+            "\n",
+            "name: halt\n",
+            "program: 99\n",
+            "memory: 99\n",
+        ))
+        .unwrap();
+        assert_eq!(2, cases.len());
+        assert_eq!(
+            vec![
+                Run {
+                    inputs: vec![5],
+                    outputs: vec![5],
+                    memory: Some(vec![5, 0, 4, 0, 99]),
+                    error: None,
+                },
+                Run {
+                    inputs: vec![],
+                    outputs: vec![1, 2],
+                    memory: None,
+                    error: Some("out".to_string()),
+                },
+            ],
+            cases[0].runs
+        );
+        assert_eq!(Some(vec![99]), cases[1].runs[0].memory);
+
+        assert!(parse("program: 99\n").is_err());
+        assert!(parse("name: a\nprogram: 99\n").is_err());
+        assert!(parse("name: a\nprogram: 99\nrun: 1\n").is_err());
+        assert!(parse("name: a\nprogram: 99\ncolor: red\n").is_err());
+
+        // memory the cpu didn't report reads as zero
+        let run = &cases[1].runs[0];
+        assert!(run.check(&Outcome::default()).is_err());
+        let mut outcome = Outcome {
+            memory: vec![99],
+            ..Outcome::default()
+        };
+        assert!(run.check(&outcome).is_ok());
+        outcome.outputs.push(1);
+        let failures = failures(&cases[1..], |_, _| outcome.clone());
+        assert_eq!(vec!["halt, run 1: outputs [1], expected []"], failures);
+    }
+}
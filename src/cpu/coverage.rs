@@ -0,0 +1,163 @@
+use super::disasm;
+use std::collections::BTreeMap;
+
+// what one or more runs executed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    // how often each instruction ran
+    pub hits: BTreeMap<u128, usize>,
+    // whether the jump or comparison at an address came out false and true
+    pub branches: BTreeMap<u128, [bool; 2]>,
+}
+
+impl Coverage {
+    pub fn hit(&mut self, pc: u128, branch: Option<bool>) {
+        *self.hits.entry(pc).or_insert(0) += 1;
+        if let Some(taken) = branch {
+            self.branches.entry(pc).or_insert([false; 2])[taken as usize] = true;
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn merge(&mut self, other: &Coverage) {
+        for (pc, hits) in other.hits.iter() {
+            *self.hits.entry(*pc).or_insert(0) += hits;
+        }
+        for (pc, seen) in other.branches.iter() {
+            let both = self.branches.entry(*pc).or_insert([false; 2]);
+            both[0] |= seen[0];
+            both[1] |= seen[1];
+        }
+    }
+
+    // branch directions seen and the directions there are, counting only
+    // the jumps and comparisons that ran
+    #[allow(dead_code)]
+    pub fn directions(&self) -> (usize, usize) {
+        let seen = self.branches.values().flatten().filter(|s| **s).count();
+        (seen, 2 * self.branches.len())
+    }
+
+    // A disassembly of `prog` with the hit count of every instruction and
+    // the directions its branches took. Addresses that never ran are
+    // disassembled too as long as they don't fall inside an instruction
+    // that did. code the runs wrote over is shown as it is in `prog`.
+    #[allow(dead_code)]
+    pub fn listing(&self, prog: &[i128]) -> String {
+        let mut out = String::new();
+        let mut pc = 0;
+        while pc < prog.len() {
+            let mut inst = disasm::decode(prog, pc);
+            let inside = (pc + 1..pc + inst.len).any(|k| self.hits.contains_key(&(k as u128)));
+            if inside {
+                inst = disasm::Inst {
+                    pc,
+                    len: 1,
+                    opcode: 0,
+                    text: format!("data {}", prog[pc]),
+                };
+            }
+
+            let hits = match self.hits.get(&(pc as u128)) {
+                Some(hits) => hits.to_string(),
+                None => "-".to_string(),
+            };
+            let branch = match (inst.opcode, self.branches.get(&(pc as u128))) {
+                (5, Some(seen)) | (6, Some(seen)) | (7, Some(seen)) | (8, Some(seen)) => {
+                    let names = if inst.opcode <= 6 {
+                        ["not taken", "taken"]
+                    } else {
+                        ["false", "true"]
+                    };
+                    match seen {
+                        [true, true] => "both".to_string(),
+                        [false, true] => format!("{} only", names[1]),
+                        _ => format!("{} only", names[0]),
+                    }
+                }
+                _ => String::new(),
+            };
+            out += format!("{:>6} {:>8}  {:<32}{}", pc, hits, inst.text, branch).trim_end();
+            out += "\n";
+            pc += inst.len;
+        }
+
+        let code = self.hits.len();
+        let (seen, directions) = self.directions();
+        out += &format!(
+            "{} instructions ran, {} of {} branch directions taken\n",
+            code, seen, directions
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod coverage_tests {
+    use super::*;
+    use crate::cpu::Cpu;
+
+    fn run(prog: &[i128], input: i128) -> Coverage {
+        let mut cpu = Cpu::new_detached(prog);
+        cpu.track_coverage();
+        let mut out = cpu.into_outputs(vec![input]);
+        out.by_ref().for_each(|v| assert!(v.is_ok()));
+        out.into_cpu().coverage().unwrap().clone()
+    }
+
+    #[test]
+    fn merged_runs() {
+        // skips the output when the input is 0
+        let prog = vec![3, 10, 1006, 10, 7, 104, 1, 99, 3333, 0, 0];
+        let mut coverage = run(&prog, 0);
+        assert_eq!((1, 2), coverage.directions());
+        assert_eq!(None, coverage.hits.get(&5));
+
+        coverage.merge(&run(&prog, 5));
+        assert_eq!((2, 2), coverage.directions());
+        assert_eq!(
+            concat!(
+                "     0        2  in [10]\n",
+                "     2        2  jz [10], 7                      both\n",
+                "     5        1  out 1\n",
+                "     7        2  hlt\n",
+                "     8        -  data 3333\n",
+                "     9        -  data 0\n",
+                "    10        -  data 0\n",
+                "4 instructions ran, 2 of 2 branch directions taken\n",
+            ),
+            coverage.listing(&prog)
+        );
+    }
+
+    #[test]
+    fn day7_permutations() {
+        let prog = crate::cpu::parse_input("resources/day7-input.txt").unwrap();
+        let mut merged = Coverage::default();
+        let mut first = None;
+
+        for n in 0..5usize.pow(5) {
+            let phases: Vec<i128> = (0..5).map(|k| (n / 5usize.pow(k) % 5) as i128).collect();
+            if (0..5).any(|p| !phases.contains(&p)) {
+                continue;
+            }
+            let mut signal = 0;
+            for phase in phases {
+                let mut cpu = Cpu::new_detached(&prog);
+                cpu.track_coverage();
+                let mut out = cpu.into_outputs(vec![phase, signal]);
+                signal = out.next().unwrap().unwrap();
+                let coverage = out.into_cpu().coverage().unwrap().clone();
+                merged.merge(&coverage);
+                first.get_or_insert(coverage);
+            }
+        }
+
+        // every phase runs its own code, none of them branches
+        let first = first.unwrap();
+        assert_eq!(8, first.hits.len());
+        assert_eq!(30, merged.hits.len());
+        assert_eq!(120 * 5, merged.hits[&0]);
+        assert_eq!((1, 2), merged.directions());
+    }
+}
@@ -0,0 +1,233 @@
+use super::disasm::{self, Inst};
+use super::record::Event;
+use super::{Cpu, Image, Step};
+use anyhow::Result;
+use std::collections::{BTreeSet, VecDeque};
+
+// how many of the last writes are remembered
+const RECENT: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum State {
+    Ready,
+    Waiting,
+    Halted,
+    Fault(String),
+}
+
+// A cpu with what a debugger shows about it: breakpoints, the cells
+// written lately and the inputs and outputs so far.
+pub struct Session {
+    pub cpu: Cpu,
+    pub breakpoints: BTreeSet<u128>,
+    // the newest write last
+    pub writes: VecDeque<u128>,
+    pub log: Vec<Event>,
+    pub state: State,
+    // first address of the disassembly, moves when pc leaves it
+    code_top: u128,
+}
+
+impl Session {
+    #[allow(dead_code)]
+    pub fn new(program: impl Into<Image>) -> Session {
+        Session {
+            cpu: Cpu::new_detached(program),
+            breakpoints: BTreeSet::new(),
+            writes: VecDeque::new(),
+            log: vec![],
+            state: State::Ready,
+            code_top: 0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn input(&mut self, v: i128) {
+        self.cpu.push_input(v);
+        if self.state == State::Waiting {
+            self.state = State::Ready;
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn toggle_breakpoint(&mut self, addr: u128) {
+        if !self.breakpoints.remove(&addr) {
+            self.breakpoints.insert(addr);
+        }
+    }
+
+    // the cell the instruction at pc is about to write, if it runs
+    fn destination(&self) -> Result<Option<u128>> {
+        let (opcode, m1, _, m3) = self.cpu.parse_instruction()?;
+        match opcode {
+            1 | 2 | 7 | 8 => self.cpu.param_addr(3, m3),
+            3 if !self.cpu.inputs.is_empty() => self.cpu.param_addr(1, m1),
+            _ => Ok(None),
+        }
+    }
+
+    fn try_step(&mut self) -> Result<()> {
+        let dest = self.destination()?;
+        let input = match self.cpu.parse_instruction()?.0 {
+            3 => self.cpu.inputs.front().copied(),
+            _ => None,
+        };
+        match self.cpu.step()? {
+            Step::Continue => {}
+            Step::Output(v) => self.log.push(Event::Output(v)),
+            Step::NeedInput => self.state = State::Waiting,
+            Step::Halt => self.state = State::Halted,
+        }
+        if let Some(v) = input {
+            self.log.push(Event::Input(v));
+        }
+        if let Some(dest) = dest {
+            self.writes.retain(|k| *k != dest);
+            self.writes.push_back(dest);
+            if self.writes.len() > RECENT {
+                self.writes.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    // runs one instruction unless the cpu waits for input, halted or faulted
+    #[allow(dead_code)]
+    pub fn step(&mut self) {
+        if self.state != State::Ready {
+            return;
+        }
+        if let Err(e) = self.try_step() {
+            self.state = State::Fault(e.to_string());
+        }
+    }
+
+    // runs up to `limit` instructions, stopping early at a breakpoint or
+    // when the cpu can't go on. `true` when it stopped early
+    #[allow(dead_code)]
+    pub fn run(&mut self, limit: usize) -> bool {
+        for _ in 0..limit {
+            self.step();
+            if self.state != State::Ready || self.breakpoints.contains(&self.cpu.pc) {
+                return true;
+            }
+        }
+        false
+    }
+
+    // `rows` instructions from the top of the disassembly, which moves to
+    // pc when pc isn't one of them
+    #[allow(dead_code)]
+    pub fn disassembly(&mut self, rows: usize) -> Vec<Inst> {
+        let mut listing = self.decode(self.code_top, rows);
+        if !listing.iter().any(|inst| inst.pc as u128 == self.cpu.pc) {
+            self.code_top = self.cpu.pc;
+            listing = self.decode(self.code_top, rows);
+        }
+        listing
+    }
+
+    fn decode(&self, top: u128, rows: usize) -> Vec<Inst> {
+        // no instruction is longer than 4 cells
+        let window: Vec<i128> = (top..top + 4 * rows as u128)
+            .map(|k| self.cpu.mem.get(k))
+            .collect();
+        let mut listing = vec![];
+        let mut pc = 0;
+        while listing.len() < rows {
+            let mut inst = disasm::decode(&window, pc);
+            pc += inst.len;
+            inst.pc += top as usize;
+            listing.push(inst);
+        }
+        listing
+    }
+
+    // `rows` rows of `width` cells from `top` in hex, each cell with
+    // whether it was written lately
+    #[allow(dead_code)]
+    pub fn memory(&self, top: u128, rows: usize, width: usize) -> Vec<Vec<(String, bool)>> {
+        (0..rows as u128)
+            .map(|row| {
+                (0..width as u128)
+                    .map(|col| {
+                        let k = top + row * width as u128 + col;
+                        let v = self.cpu.mem.get(k);
+                        let text = if v < 0 {
+                            format!("-{:x}", -v)
+                        } else {
+                            format!("{:x}", v)
+                        };
+                        (text, self.writes.contains(&k))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod debug_tests {
+    use super::*;
+
+    // outputs twice each input until it reads a 0
+    fn doubler() -> Vec<i128> {
+        vec![
+            3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+        ]
+    }
+
+    #[test]
+    fn steps() {
+        let mut session = Session::new(&doubler());
+        session.step();
+        assert_eq!(State::Waiting, session.state);
+        assert_eq!(0, session.cpu.pc);
+
+        session.input(4);
+        session.toggle_breakpoint(9);
+        assert!(session.run(100));
+        assert_eq!(9, session.cpu.pc);
+        assert_eq!(vec![15], Vec::from(session.writes.clone()));
+        assert_eq!(
+            vec![("8".to_string(), true), ("0".to_string(), false)],
+            session.memory(15, 1, 2)[0]
+        );
+
+        session.toggle_breakpoint(9);
+        assert!(session.run(100));
+        assert_eq!(State::Waiting, session.state);
+        session.input(0);
+        assert!(session.run(100));
+        assert_eq!(State::Halted, session.state);
+        assert_eq!(
+            vec![Event::Input(4), Event::Output(8), Event::Input(0)],
+            session.log
+        );
+    }
+
+    #[test]
+    fn follows_pc() {
+        let mut session = Session::new(&doubler());
+        let text: Vec<String> = session
+            .disassembly(3)
+            .into_iter()
+            .map(|inst| inst.text)
+            .collect();
+        assert_eq!(vec!["in [15]", "jz [15], 14", "mul [15], 2, [15]"], text);
+
+        session.input(0);
+        session.run(100);
+        assert_eq!(14, session.cpu.pc);
+        let listing = session.disassembly(3);
+        assert_eq!((14, "hlt"), (listing[0].pc, listing[0].text.as_str()));
+        assert_eq!("data 0", listing[1].text);
+
+        let mut session = Session::new(&vec![1101, 1, 1, 3]);
+        session.run(100);
+        assert_eq!(
+            State::Fault("unknown opcode '0' at '4'".to_string()),
+            session.state
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::Result;
+
+type Build<T> = fn(&[i128]) -> Result<T>;
+
+// a complete tuple together with the record built from it. the raw values
+// are kept so a logged stream can be fed to a consumer again
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event<T> {
+    pub values: Vec<i128>,
+    pub record: T,
+}
+
+// Groups output values into tuples of a fixed arity. Each tuple becomes a
+// record of the first rule whose prefix it matches, `None` in a prefix
+// matches any value.
+//
+//   Decoder::new(3)
+//       .rule(&[Some(-1), Some(0)], |v| Ok(Score(v[2])))
+//       .rule(&[], |v| Ok(Tile(v[0], v[1], v[2])))
+pub struct Decoder<T> {
+    arity: usize,
+    rules: Vec<(Vec<Option<i128>>, Build<T>)>,
+    pending: Vec<i128>,
+}
+
+impl<T> Decoder<T> {
+    #[allow(dead_code)]
+    pub fn new(arity: usize) -> Decoder<T> {
+        Decoder {
+            arity,
+            rules: vec![],
+            pending: vec![],
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn rule(mut self, prefix: &[Option<i128>], build: Build<T>) -> Decoder<T> {
+        self.rules.push((prefix.to_vec(), build));
+        self
+    }
+
+    // a record once the tuple is complete
+    #[allow(dead_code)]
+    pub fn push(&mut self, value: i128) -> Result<Option<Event<T>>> {
+        self.pending.push(value);
+        if self.pending.len() < self.arity {
+            return Ok(None);
+        }
+
+        let values = std::mem::take(&mut self.pending);
+        let build = self
+            .rules
+            .iter()
+            .find(|(prefix, _)| {
+                prefix
+                    .iter()
+                    .zip(values.iter())
+                    .all(|(p, v)| p.is_none() || *p == Some(*v))
+            })
+            .map(|(_, build)| *build)
+            .ok_or_else(|| anyhow::anyhow!("no rule for tuple {:?}", values))?;
+
+        let record = build(&values)?;
+        Ok(Some(Event { values, record }))
+    }
+
+    #[allow(dead_code)]
+    pub fn pending(&self) -> &[i128] {
+        &self.pending
+    }
+
+    // to be called once the cpu halted, a partial tuple is an error
+    #[allow(dead_code)]
+    pub fn finish(&self) -> Result<()> {
+        if !self.pending.is_empty() {
+            anyhow::bail!("incomplete tuple {:?} at halt", self.pending);
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn decode<I>(self, values: I) -> Events<I::IntoIter, T>
+    where
+        I: IntoIterator<Item = Result<i128>>,
+    {
+        Events {
+            decoder: self,
+            values: values.into_iter(),
+            done: false,
+        }
+    }
+}
+
+// decoded records of an output stream, e.g. `cpu::outputs`
+#[allow(dead_code)]
+pub struct Events<I, T> {
+    decoder: Decoder<T>,
+    values: I,
+    done: bool,
+}
+
+impl<I, T> Iterator for Events<I, T>
+where
+    I: Iterator<Item = Result<i128>>,
+{
+    type Item = Result<Event<T>>;
+
+    fn next(&mut self) -> Option<Result<Event<T>>> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let result = match self.values.next() {
+                Some(Ok(value)) => self.decoder.push(value).transpose(),
+                Some(Err(e)) => Some(Err(e)),
+                None => {
+                    self.done = true;
+                    return self.decoder.finish().err().map(Err);
+                }
+            };
+
+            if let Some(result) = result {
+                self.done = result.is_err();
+                return Some(result);
+            }
+        }
+    }
+}
+
+// the raw output values of logged events, in order
+#[allow(dead_code)]
+pub fn replay<T>(events: &[Event<T>]) -> impl Iterator<Item = i128> + '_ {
+    events.iter().flat_map(|event| event.values.iter().copied())
+}
+
+#[cfg(test)]
+mod decoder_tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Screen {
+        Score(i128),
+        Tile(i128, i128, i128),
+    }
+    use Screen::*;
+
+    fn screen() -> Decoder<Screen> {
+        Decoder::new(3)
+            .rule(&[Some(-1), Some(0)], |v| Ok(Score(v[2])))
+            .rule(&[None, None, Some(9)], |v| {
+                anyhow::bail!("bad tile at {}, {}", v[0], v[1])
+            })
+            .rule(&[], |v| Ok(Tile(v[0], v[1], v[2])))
+    }
+
+    #[test]
+    fn sentinel_rules() {
+        let values = vec![1, 2, 3, -1, 0, 500, -1, 1, 4];
+        let records: Vec<Screen> = screen()
+            .decode(values.into_iter().map(Ok))
+            .map(|event| event.unwrap().record)
+            .collect();
+
+        assert_eq!(vec![Tile(1, 2, 3), Score(500), Tile(-1, 1, 4)], records);
+    }
+
+    #[test]
+    fn incomplete_tuple() {
+        let mut decoder = screen();
+        assert_eq!(None, decoder.push(1).unwrap());
+        assert_eq!(None, decoder.push(2).unwrap());
+        assert!(decoder.push(3).unwrap().is_some());
+        decoder.push(4).unwrap();
+        assert_eq!(&[4], decoder.pending());
+        assert!(decoder.finish().is_err());
+
+        let events: Vec<_> = screen().decode(vec![Ok(1), Ok(2), Ok(3), Ok(4)]).collect();
+        assert_eq!(2, events.len());
+        assert!(events[0].is_ok());
+        assert!(events[1].is_err());
+    }
+
+    #[test]
+    fn failing_rules() {
+        let mut events = screen().decode(vec![Ok(1), Ok(2), Ok(9), Ok(1), Ok(2), Ok(3)]);
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+
+        let mut pairs: Decoder<i128> = Decoder::new(2).rule(&[Some(0)], |v| Ok(v[1]));
+        assert!(pairs.push(1).unwrap().is_none());
+        assert!(pairs.push(1).is_err());
+    }
+
+    #[test]
+    fn replay_program() {
+        // prints a tile and the score
+        let prog = vec![104, 1, 104, 2, 104, 3, 104, -1, 104, 0, 104, 7, 99];
+        let events: Vec<Event<Screen>> = screen()
+            .decode(crate::cpu::outputs(&prog, vec![]))
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(Score(7), events[1].record);
+        assert_eq!(
+            vec![1, 2, 3, -1, 0, 7],
+            replay(&events).collect::<Vec<i128>>()
+        );
+
+        let prog = vec![104, 1, 104, 2, 104, 3, 104, -1, 104, 0, 99];
+        assert!(screen()
+            .decode(crate::cpu::outputs(&prog, vec![]))
+            .any(|event| event.is_err()));
+    }
+}
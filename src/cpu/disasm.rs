@@ -0,0 +1,83 @@
+use super::lint;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inst {
+    pub pc: usize,
+    pub len: usize,
+    pub opcode: i128,
+    pub text: String,
+}
+
+fn mnemonic(opcode: i128) -> Option<(&'static str, usize)> {
+    match opcode {
+        1 => Some(("add", 3)),
+        2 => Some(("mul", 3)),
+        3 => Some(("in", 1)),
+        4 => Some(("out", 1)),
+        5 => Some(("jnz", 2)),
+        6 => Some(("jz", 2)),
+        7 => Some(("lt", 3)),
+        8 => Some(("eq", 3)),
+        9 => Some(("arb", 1)),
+        99 => Some(("hlt", 0)),
+        _ => None,
+    }
+}
+
+// The instruction at `pc`, parameters written as `[addr]` in position
+// mode, `[rb+off]` in relative mode and plainly when immediate. Cells that
+// are no instruction come out as `data`.
+pub fn decode(mem: &[i128], pc: usize) -> Inst {
+    let inst = mem.get(pc).copied().unwrap_or(0);
+    let data = Inst {
+        pc,
+        len: 1,
+        opcode: 0,
+        text: format!("data {}", inst),
+    };
+    let (name, params) = match mnemonic(inst % 100) {
+        Some(m) if inst >= 0 => m,
+        _ => return data,
+    };
+    if lint::check(inst)
+        .iter()
+        .any(|p| matches!(p, lint::Problem::BadMode(..)))
+    {
+        return data;
+    }
+
+    let mut args = vec![];
+    for n in 1..=params {
+        let v = mem.get(pc + n).copied().unwrap_or(0);
+        args.push(match inst / 10i128.pow(n as u32 + 1) % 10 {
+            0 => format!("[{}]", v),
+            1 => v.to_string(),
+            _ if v < 0 => format!("[rb{}]", v),
+            _ => format!("[rb+{}]", v),
+        });
+    }
+    Inst {
+        pc,
+        len: params + 1,
+        opcode: inst % 100,
+        text: format!("{} {}", name, args.join(", "))
+            .trim_end()
+            .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod disasm_tests {
+    use super::*;
+
+    #[test]
+    fn instructions() {
+        let mem = vec![1002, 4, -3, 4, 21101, 1, 2, -1, 99, 3333];
+        assert_eq!("mul [4], -3, [4]", decode(&mem, 0).text);
+        assert_eq!(4, decode(&mem, 0).len);
+        assert_eq!("add 1, 2, [rb-1]", decode(&mem, 4).text);
+        assert_eq!("hlt", decode(&mem, 8).text);
+        assert_eq!("data 3333", decode(&mem, 9).text);
+        assert_eq!("data 0", decode(&mem, 10).text);
+    }
+}
@@ -0,0 +1,182 @@
+use super::Cpu;
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+// cells in a row of the table
+const ROW: usize = 8;
+
+// A copy of a range of memory. As text it's a table with the address of
+// each row, the cells and the cells read as characters:
+//
+//     0:   3   8 1005   8  |....|
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dump {
+    pub cells: BTreeMap<u128, i128>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Change {
+    pub addr: u128,
+    pub before: i128,
+    pub after: i128,
+}
+
+impl Cpu {
+    // the cells in `start..end`
+    #[allow(dead_code)]
+    pub fn dump(&self, start: u128, end: u128) -> Dump {
+        Dump {
+            cells: (start..end).map(|k| (k, self.mem.get(k))).collect(),
+        }
+    }
+}
+
+impl Dump {
+    #[allow(dead_code)]
+    pub fn table(&self) -> String {
+        let width = self
+            .cells
+            .values()
+            .map(|v| v.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let addrs: Vec<u128> = self.cells.keys().copied().collect();
+        let addr_width = addrs.last().map_or(0, |k| k.to_string().len());
+        let mut out = String::new();
+        for row in addrs.chunks(ROW) {
+            let values: Vec<String> = row
+                .iter()
+                .map(|k| format!("{:>w$}", self.cells[k], w = width))
+                .collect();
+            let text: String = row
+                .iter()
+                .map(|k| match self.cells[k] {
+                    v if (32..127).contains(&v) => v as u8 as char,
+                    _ => '.',
+                })
+                .collect();
+            let pad = " ".repeat((ROW - row.len()) * (width + 1));
+            out += &format!(
+                "{:>aw$}: {}{}  |{}|\n",
+                row[0],
+                values.join(" "),
+                pad,
+                text,
+                aw = addr_width
+            );
+        }
+        out
+    }
+
+    #[allow(dead_code)]
+    pub fn parse(text: &str) -> Result<Dump> {
+        let mut cells = BTreeMap::new();
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (addr, rest) = match line.split_once(':') {
+                Some((addr, rest)) => (addr.trim().parse::<u128>()?, rest),
+                None => anyhow::bail!("no address on line {}", n + 1),
+            };
+            // the characters start at the first bar
+            let values = rest.split('|').next().unwrap_or("");
+            for (k, v) in values.split_whitespace().enumerate() {
+                cells.insert(addr + k as u128, v.parse()?);
+            }
+        }
+        Ok(Dump { cells })
+    }
+
+    #[allow(dead_code)]
+    pub fn save(&self, path: &str) -> Result<()> {
+        Ok(std::fs::write(path, self.table())?)
+    }
+
+    #[allow(dead_code)]
+    pub fn load(path: &str) -> Result<Dump> {
+        Dump::parse(&std::fs::read_to_string(path)?)
+    }
+
+    // the cells that differ from `earlier`, a cell only one of the dumps
+    // has counts as 0 in the other like unwritten memory
+    #[allow(dead_code)]
+    pub fn diff(&self, earlier: &Dump) -> Vec<Change> {
+        let mut addrs: Vec<u128> = earlier
+            .cells
+            .keys()
+            .chain(self.cells.keys())
+            .copied()
+            .collect();
+        addrs.sort_unstable();
+        addrs.dedup();
+        addrs
+            .into_iter()
+            .map(|addr| Change {
+                addr,
+                before: earlier.cells.get(&addr).copied().unwrap_or(0),
+                after: self.cells.get(&addr).copied().unwrap_or(0),
+            })
+            .filter(|c| c.before != c.after)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod dump_tests {
+    use super::*;
+    use crate::cpu::Step;
+
+    #[test]
+    fn table() {
+        let cpu = Cpu::new_detached(&vec![104, 72, 104, 105, 99, -1, 10, 1234567]);
+        let dump = cpu.dump(0, 10);
+        assert_eq!(
+            concat!(
+                "0:     104      72     104     105      99      -1      10 1234567  |hHhic...|\n",
+                "8:       0       0                                                  |..|\n",
+            ),
+            dump.table()
+        );
+        assert_eq!(dump, Dump::parse(&dump.table()).unwrap());
+        assert!(Dump::parse("1 2 3").is_err());
+        assert_eq!(
+            vec![10, 11, 12],
+            Dump::parse("10: 1 2 3 |...|")
+                .unwrap()
+                .cells
+                .keys()
+                .copied()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn day13_frame() {
+        let mut prog = crate::cpu::parse_input("resources/day13-input.txt").unwrap();
+        prog[0] = 2;
+        let mut cpu = Cpu::new_detached(&prog);
+        let frame = |cpu: &mut Cpu| {
+            while cpu.step().unwrap() != Step::NeedInput {}
+            cpu.dump(0, prog.len() as u128)
+        };
+
+        let before = frame(&mut cpu);
+        cpu.push_input(0);
+        let after = frame(&mut cpu);
+        let changes = after.diff(&before);
+
+        // the ball moves one step along x and y
+        let ball = changes.iter().find(|c| c.addr == 388).unwrap();
+        assert_eq!(1, (ball.after - ball.before).abs());
+        assert!(changes.len() < 20, "{:?}", changes);
+        assert!(after.diff(&after).is_empty());
+
+        let name = format!("day13-frame-{}.dump", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let path = path.to_str().unwrap();
+        after.save(path).unwrap();
+        assert!(Dump::load(path).unwrap().diff(&after).is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,507 @@
+use super::{Cpu, Step};
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// every cell takes 16 bytes of the address space gdb sees, little endian
+const CELL: u128 = 16;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.intcode.cpu">
+    <reg name="pc" bitsize="64" type="code_ptr" regnum="0"/>
+    <reg name="base" bitsize="64" type="int64" regnum="1"/>
+    <reg name="count" bitsize="64" type="uint64" regnum="2"/>
+  </feature>
+</target>
+"#;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Result<Vec<u8>> {
+    if s.len() % 2 == 1 {
+        anyhow::bail!("odd hex '{}'", s);
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|k| Ok(u8::from_str_radix(&s[k..k + 2], 16)?))
+        .collect()
+}
+
+fn number(s: &str) -> Result<u128> {
+    Ok(u128::from_str_radix(s, 16)?)
+}
+
+// A gdb remote serial protocol server for one cpu. The registers are the
+// pc as a byte address, the relative base and the instruction count, all
+// 64 bits. Inputs are given with `monitor input 1 2 3`, outputs show up on
+// the gdb console. A read with no input pending stops the cpu, so does an
+// illegal instruction.
+pub struct Stub {
+    cpu: Cpu,
+    breakpoints: BTreeSet<u128>,
+    halted: bool,
+    // the signal of the last stop, 4 after an illegal instruction
+    signal: u8,
+}
+
+impl Stub {
+    #[allow(dead_code)]
+    pub fn new(cpu: Cpu) -> Stub {
+        Stub {
+            cpu,
+            breakpoints: BTreeSet::new(),
+            halted: false,
+            signal: 5,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn into_cpu(self) -> Cpu {
+        self.cpu
+    }
+
+    // serves the first client until it detaches, kills or hangs up
+    #[allow(dead_code)]
+    pub fn serve(&mut self, listener: &TcpListener) -> Result<()> {
+        let (mut stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        while let Some(packet) = read_packet(&mut stream)? {
+            stream.write_all(b"+")?;
+            match packet.as_str() {
+                "k" => break,
+                "D" => {
+                    write_packet(&mut stream, "OK")?;
+                    break;
+                }
+                _ => {
+                    let reply = self
+                        .command(&packet, &mut stream)
+                        .unwrap_or_else(|_| "E01".to_string());
+                    write_packet(&mut stream, &reply)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn stop_reply(&self) -> String {
+        if self.halted {
+            "W00".to_string()
+        } else {
+            format!("S{:02x}", self.signal)
+        }
+    }
+
+    fn registers(&self) -> [u64; 3] {
+        [
+            self.cpu.pc.wrapping_mul(CELL) as u64,
+            self.cpu.base as i64 as u64,
+            self.cpu.count,
+        ]
+    }
+
+    fn set_register(&mut self, n: usize, v: u64) -> Result<()> {
+        match n {
+            0 => self.cpu.pc = v as u128 / CELL,
+            1 => self.cpu.base = v as i64 as i128,
+            2 => self.cpu.count = v,
+            _ => anyhow::bail!("no register {}", n),
+        }
+        Ok(())
+    }
+
+    // the addresses of `len` bytes from `addr`, `None` when they run past
+    // the last one
+    fn span(addr: u128, len: usize) -> Option<impl Iterator<Item = u128>> {
+        let last = match len {
+            0 => addr,
+            _ => addr.checked_add(len as u128 - 1)?,
+        };
+        Some((addr..=last).take(len))
+    }
+
+    fn read_memory(&self, addr: u128, len: u128) -> Option<Vec<u8>> {
+        let span = Stub::span(addr, len as usize)?;
+        Some(
+            span.map(|a| self.cpu.mem.get(a / CELL).to_le_bytes()[(a % CELL) as usize])
+                .collect(),
+        )
+    }
+
+    fn write_memory(&mut self, addr: u128, bytes: &[u8]) -> Option<()> {
+        for (a, byte) in Stub::span(addr, bytes.len())?.zip(bytes) {
+            let mut cell = self.cpu.mem.get(a / CELL).to_le_bytes();
+            cell[(a % CELL) as usize] = *byte;
+            self.cpu.mem.set(a / CELL, i128::from_le_bytes(cell));
+        }
+        Some(())
+    }
+
+    // runs one instruction, outputs go to the gdb console. `false` when the
+    // cpu can't go on
+    fn step(&mut self, stream: &mut TcpStream) -> Result<bool> {
+        if self.halted {
+            return Ok(false);
+        }
+        self.signal = 5;
+        let step = match self.cpu.step() {
+            Ok(step) => step,
+            Err(e) => {
+                let line = format!("{}\n", e);
+                write_packet(stream, &format!("O{}", hex(line.as_bytes())))?;
+                self.signal = 4;
+                return Ok(false);
+            }
+        };
+        match step {
+            Step::Continue => Ok(true),
+            Step::Output(v) => {
+                let line = format!("output {}\n", v);
+                write_packet(stream, &format!("O{}", hex(line.as_bytes())))?;
+                Ok(true)
+            }
+            Step::NeedInput => Ok(false),
+            Step::Halt => {
+                self.halted = true;
+                Ok(false)
+            }
+        }
+    }
+
+    fn resume(&mut self, stream: &mut TcpStream) -> Result<String> {
+        let mut steps = 0u64;
+        while self.step(stream)? {
+            if self.breakpoints.contains(&self.cpu.pc) {
+                break;
+            }
+            // gdb sends 0x03 to interrupt a run
+            steps += 1;
+            if steps == 10000 && interrupted(stream)? {
+                return Ok("S02".to_string());
+            }
+            steps %= 10000;
+        }
+        Ok(self.stop_reply())
+    }
+
+    fn monitor(&mut self, command: &str) -> Result<String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let text = match words[..] {
+            ["input", ..] => {
+                for w in words[1..].iter() {
+                    self.cpu.push_input(w.parse()?);
+                }
+                return Ok("OK".to_string());
+            }
+            _ => format!("unknown command '{}', try 'input <values>'\n", command),
+        };
+        Ok(hex(text.as_bytes()))
+    }
+
+    fn command(&mut self, packet: &str, stream: &mut TcpStream) -> Result<String> {
+        // an empty packet or one that starts with more than a byte has no
+        // command
+        let (head, rest) = match (packet.get(..1), packet.get(1..)) {
+            (Some(head), Some(rest)) => (head, rest),
+            _ => return Ok(String::new()),
+        };
+        Ok(match head {
+            "?" => self.stop_reply(),
+            "g" => hex(&self
+                .registers()
+                .iter()
+                .flat_map(|r| r.to_le_bytes().to_vec())
+                .collect::<Vec<u8>>()),
+            "G" => {
+                let bytes = unhex(rest)?;
+                for (n, chunk) in bytes.chunks(8).enumerate() {
+                    let mut value = [0; 8];
+                    value[..chunk.len()].copy_from_slice(chunk);
+                    self.set_register(n, u64::from_le_bytes(value))?;
+                }
+                "OK".to_string()
+            }
+            "p" => {
+                let n = number(rest)? as usize;
+                match self.registers().get(n) {
+                    Some(r) => hex(&r.to_le_bytes()),
+                    None => "E01".to_string(),
+                }
+            }
+            "P" => {
+                let (n, value) = match rest.split('=').collect::<Vec<_>>()[..] {
+                    [n, value] => (number(n)? as usize, unhex(value)?),
+                    _ => anyhow::bail!("bad register write '{}'", rest),
+                };
+                let mut bytes = [0; 8];
+                bytes[..value.len().min(8)].copy_from_slice(&value[..value.len().min(8)]);
+                self.set_register(n, u64::from_le_bytes(bytes))?;
+                "OK".to_string()
+            }
+            "m" => {
+                let (addr, len) = match rest.split(',').collect::<Vec<_>>()[..] {
+                    [addr, len] => (number(addr)?, number(len)?.min(4096)),
+                    _ => anyhow::bail!("bad memory read '{}'", rest),
+                };
+                match self.read_memory(addr, len) {
+                    Some(bytes) => hex(&bytes),
+                    None => "E01".to_string(),
+                }
+            }
+            "M" => {
+                let (addr, data) = match rest.split([',', ':']).collect::<Vec<_>>()[..] {
+                    [addr, _, data] => (number(addr)?, unhex(data)?),
+                    _ => anyhow::bail!("bad memory write '{}'", rest),
+                };
+                match self.write_memory(addr, &data) {
+                    Some(()) => "OK".to_string(),
+                    None => "E01".to_string(),
+                }
+            }
+            "s" => {
+                self.step(stream)?;
+                self.stop_reply()
+            }
+            "c" => self.resume(stream)?,
+            "Z" | "z" => {
+                let addr = match rest.split(',').collect::<Vec<_>>()[..] {
+                    ["0", addr, _] | ["1", addr, _] => number(addr)? / CELL,
+                    _ => return Ok(String::new()),
+                };
+                if head == "Z" {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                "OK".to_string()
+            }
+            "H" => "OK".to_string(),
+            "q" if rest.starts_with("Supported") => {
+                "PacketSize=1000;qXfer:features:read+".to_string()
+            }
+            "q" if rest == "Attached" => "1".to_string(),
+            "q" if rest.starts_with("Xfer:features:read:target.xml:") => {
+                let range = rest.trim_start_matches("Xfer:features:read:target.xml:");
+                let (offset, len) = match range.split(',').collect::<Vec<_>>()[..] {
+                    [offset, len] => (number(offset)? as usize, number(len)? as usize),
+                    _ => anyhow::bail!("bad read '{}'", range),
+                };
+                let start = offset.min(TARGET_XML.len());
+                let end = (offset + len).min(TARGET_XML.len());
+                let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                format!("{}{}", more, &TARGET_XML[start..end])
+            }
+            "q" if rest.starts_with("Rcmd,") => {
+                let command = String::from_utf8(unhex(&rest[5..])?)?;
+                self.monitor(&command)?
+            }
+            // anything else is unsupported
+            _ => String::new(),
+        })
+    }
+}
+
+// the next packet without its framing, `None` once the client hangs up. a
+// packet with a bad checksum is refused and sent again by the client
+fn read_packet(stream: &mut TcpStream) -> Result<Option<String>> {
+    let mut byte = [0u8];
+    loop {
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+
+        let mut data = vec![];
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'#' {
+                break;
+            }
+            data.push(byte[0]);
+        }
+        let mut checksum = [0u8; 2];
+        stream.read_exact(&mut checksum)?;
+
+        let sum = data.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        if format!("{:02x}", sum) == String::from_utf8_lossy(&checksum).to_lowercase() {
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        stream.write_all(b"-")?;
+    }
+}
+
+fn write_packet(stream: &mut TcpStream, data: &str) -> Result<()> {
+    let sum = data.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+    stream.write_all(format!("${}#{:02x}", data, sum).as_bytes())?;
+    Ok(())
+}
+
+fn interrupted(stream: &mut TcpStream) -> Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut byte = [0u8];
+    let read = stream.read(&mut byte);
+    stream.set_nonblocking(false)?;
+    match read {
+        Ok(1) => Ok(byte[0] == 0x03),
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod gdb_tests {
+    use super::*;
+    use std::thread;
+
+    // a stub for `prog` on a free port, the thread hands the cpu back
+    fn connect(prog: Vec<i128>) -> (TcpStream, thread::JoinHandle<Cpu>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut stub = Stub::new(Cpu::new_detached(&prog));
+            stub.serve(&listener).unwrap();
+            stub.into_cpu()
+        });
+        let client = TcpStream::connect(addr).unwrap();
+        client.set_nodelay(true).unwrap();
+        (client, server)
+    }
+
+    // the console output and the reply to one packet
+    fn send(stream: &mut TcpStream, data: &str) -> (String, String) {
+        write_packet(stream, data).unwrap();
+        let mut console = String::new();
+        loop {
+            let reply = read_packet(stream).unwrap().unwrap();
+            stream.write_all(b"+").unwrap();
+            match reply.strip_prefix('O') {
+                Some(text) if !reply.starts_with("OK") => {
+                    console += &String::from_utf8(unhex(text).unwrap()).unwrap();
+                }
+                _ => return (console, reply),
+            }
+        }
+    }
+
+    fn reply(stream: &mut TcpStream, data: &str) -> String {
+        send(stream, data).1
+    }
+
+    #[test]
+    fn session() {
+        // outputs twice each input until it reads a 0
+        let prog = vec![
+            3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+        ];
+        let (mut client, server) = connect(prog);
+
+        assert!(reply(&mut client, "qSupported:multiprocess+").contains("qXfer"));
+        assert_eq!("S05", reply(&mut client, "?"));
+        assert_eq!("0".repeat(48), reply(&mut client, "g"));
+
+        // cell 15 is bytes 0xf0 to 0xff
+        assert_eq!("OK", reply(&mut client, "Mf0,2:0701"));
+        assert_eq!("0701000000", reply(&mut client, "mf0,5"));
+        assert_eq!("0e", reply(&mut client, "m40,1"));
+
+        // no input yet, so the read stays put
+        assert_eq!("S05", reply(&mut client, "s"));
+        assert_eq!("0000000000000000", reply(&mut client, "p0"));
+        assert_eq!(
+            "OK",
+            reply(&mut client, &format!("qRcmd,{}", hex(b"input 4 0")))
+        );
+
+        assert_eq!("OK", reply(&mut client, "Z0,90,1"));
+        assert_eq!("S05", reply(&mut client, "c"));
+        assert_eq!("9000000000000000", reply(&mut client, "p0"));
+        assert_eq!(
+            ("output 8\n".to_string(), "S05".to_string()),
+            send(&mut client, "s")
+        );
+
+        assert_eq!("OK", reply(&mut client, "P1=0500000000000000"));
+        assert_eq!("0500000000000000", reply(&mut client, "p1"));
+        assert_eq!("OK", reply(&mut client, "z0,90,1"));
+        assert_eq!("W00", reply(&mut client, "c"));
+        assert_eq!("W00", reply(&mut client, "s"));
+        assert_eq!("OK", reply(&mut client, "D"));
+
+        let cpu = server.join().unwrap();
+        assert_eq!(0, cpu.get_mem(15).unwrap());
+        assert_eq!(8, cpu.count());
+    }
+
+    #[test]
+    fn faults() {
+        let (mut client, server) = connect(vec![1101, 40, 2, 4, 99]);
+
+        let xml = reply(&mut client, "qXfer:features:read:target.xml:0,20");
+        assert_eq!(format!("m{}", &TARGET_XML[..0x20]), xml);
+        let rest = reply(&mut client, "qXfer:features:read:target.xml:20,1000");
+        assert_eq!(format!("l{}", &TARGET_XML[0x20..]), rest);
+        assert_eq!("", reply(&mut client, "vMustReplyEmpty"));
+        assert_eq!("E01", reply(&mut client, "mzz,1"));
+
+        // ranges past the last address
+        let last = "f".repeat(32);
+        assert_eq!("E01", reply(&mut client, &format!("m{},2", last)));
+        assert_eq!("E01", reply(&mut client, &format!("M{},2:0102", last)));
+        assert_eq!("00", reply(&mut client, &format!("m{},1", last)));
+
+        // the add writes 42 over the halt at 4
+        let (console, stop) = send(&mut client, "c");
+        assert_eq!("S04", stop);
+        assert_eq!("unknown opcode '42' at '4'\n", console);
+        assert_eq!("4000000000000000", reply(&mut client, "p0"));
+
+        write_packet(&mut client, "k").unwrap();
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn far_jump() {
+        // the address of pc 2^124 + 1 doesn't fit a register and wraps
+        let (mut client, server) = connect(vec![1105, 1, (1 << 124) + 1]);
+
+        assert_eq!("S05", reply(&mut client, "s"));
+        assert_eq!("1000000000000000", reply(&mut client, "p0"));
+
+        write_packet(&mut client, "k").unwrap();
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn bad_packets() {
+        let (mut client, server) = connect(vec![99]);
+
+        // refused, then sent again
+        client.write_all(b"$g#00").unwrap();
+        let mut byte = [0u8];
+        client.read_exact(&mut byte).unwrap();
+        assert_eq!(b'-', byte[0]);
+        assert_eq!("0".repeat(48), reply(&mut client, "g"));
+
+        assert_eq!("", reply(&mut client, ""));
+        assert_eq!("", reply(&mut client, "\u{e9}1"));
+        assert_eq!("S05", reply(&mut client, "?"));
+
+        write_packet(&mut client, "k").unwrap();
+        drop(client);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+use super::{Cpu, Image, Step};
+use anyhow::Result;
+use std::fmt;
+
+// how a program talks over its inputs and outputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Numeric,
+    // lines of text as character codes
+    Ascii,
+    // outputs come in groups, like x, y, tile
+    Tuple(usize),
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Protocol::Numeric => write!(f, "numeric"),
+            Protocol::Ascii => write!(f, "ascii"),
+            Protocol::Tuple(n) => write!(f, "tuple {}", n),
+        }
+    }
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Protocol> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        Ok(match words[..] {
+            ["numeric"] => Protocol::Numeric,
+            ["ascii"] => Protocol::Ascii,
+            ["tuple", n] => Protocol::Tuple(n.parse()?),
+            _ => anyhow::bail!("unknown protocol '{}'", s),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub inputs: Vec<i128>,
+    pub outputs: Vec<i128>,
+}
+
+// What a program expects, kept in `#` lines before the program:
+//
+//     # name: BOOST
+//     # io: numeric
+//     # patch: mem[0]=2
+//     # sample: 1 -> 3063082071
+//
+// every key but the name can be repeated or left out, the samples list
+// inputs and outputs comma separated
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Header {
+    pub name: Option<String>,
+    pub protocol: Option<Protocol>,
+    pub patches: Vec<(usize, i128)>,
+    pub samples: Vec<Sample>,
+}
+
+fn numbers(s: &str) -> Result<Vec<i128>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| Ok(v.parse()?))
+        .collect()
+}
+
+fn join(values: &[i128]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+impl Header {
+    // the header lines of `text`, the program after them is left alone
+    pub fn parse(text: &str) -> Result<Header> {
+        let mut header = Header::default();
+        for line in text.lines().take_while(|l| l.starts_with('#')) {
+            let line = line.trim_start_matches('#').trim();
+            let (key, value) = match line.find(':') {
+                Some(n) => (line[..n].trim(), line[n + 1..].trim()),
+                None if line.is_empty() => continue,
+                None => anyhow::bail!("no key in header line '{}'", line),
+            };
+            match key {
+                "name" => header.name = Some(value.to_string()),
+                "io" => header.protocol = Some(value.parse()?),
+                "patch" => {
+                    let patch = value
+                        .strip_prefix("mem[")
+                        .and_then(|p| p.split_once("]="))
+                        .map(|(addr, v)| Ok::<_, anyhow::Error>((addr.parse()?, v.parse()?)));
+                    match patch {
+                        Some(patch) => header.patches.push(patch?),
+                        None => anyhow::bail!("bad patch '{}'", value),
+                    }
+                }
+                "sample" => {
+                    let (inputs, outputs) = match value.split_once("->") {
+                        Some((i, o)) => (numbers(i)?, numbers(o)?),
+                        None => anyhow::bail!("bad sample '{}'", value),
+                    };
+                    header.samples.push(Sample { inputs, outputs });
+                }
+                _ => anyhow::bail!("unknown header key '{}'", key),
+            }
+        }
+        Ok(header)
+    }
+
+    #[allow(dead_code)]
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        if let Some(name) = &self.name {
+            out += &format!("# name: {}\n", name);
+        }
+        if let Some(protocol) = &self.protocol {
+            out += &format!("# io: {}\n", protocol);
+        }
+        for (addr, v) in self.patches.iter() {
+            out += &format!("# patch: mem[{}]={}\n", addr, v);
+        }
+        for sample in self.samples.iter() {
+            let io = format!("{} -> {}", join(&sample.inputs), join(&sample.outputs));
+            out += &format!("# sample: {}\n", io.trim());
+        }
+        out
+    }
+
+    #[allow(dead_code)]
+    pub fn apply(&self, prog: &mut Vec<i128>) {
+        for (addr, v) in self.patches.iter() {
+            if *addr >= prog.len() {
+                prog.resize(addr + 1, 0);
+            }
+            prog[*addr] = *v;
+        }
+    }
+}
+
+// a program file with its header, if it has one. the patches aren't applied
+pub fn load(fname: &str) -> Result<(Header, Vec<i128>)> {
+    let text = std::fs::read_to_string(fname)?;
+    Ok((Header::parse(&text)?, body(&text)?))
+}
+
+// the program after the header, it can be wrapped over lines
+fn body(text: &str) -> Result<Vec<i128>> {
+    let mut prog = vec![];
+    for line in text.lines().skip_while(|l| l.starts_with('#')) {
+        if line.starts_with('#') {
+            anyhow::bail!("header line '{}' after the program", line);
+        }
+        match numbers(line) {
+            Ok(values) => prog.extend(values),
+            Err(e) => anyhow::bail!("parse error: {}", e),
+        }
+    }
+    Ok(prog)
+}
+
+// Guesses the protocol from the first outputs of a run that gets `inputs`
+// and zeros after them. Mostly printable text with line breaks is ascii,
+// outputs that come in groups of the same size between reads are tuples,
+// and so is one long burst that splits into triples or pairs where one
+// column has few distinct values and another many.
+#[allow(dead_code)]
+pub fn fingerprint(program: impl Into<Image>, inputs: &[i128]) -> Protocol {
+    const MAX_OUTPUTS: usize = 512;
+    const MAX_READS: usize = 16;
+    const MAX_STEPS: usize = 1_000_000;
+
+    let mut cpu = Cpu::new_detached(program);
+    let mut inputs = inputs.iter().copied();
+    let mut groups: Vec<Vec<i128>> = vec![vec![]];
+    let mut reads = 0;
+    for _ in 0..MAX_STEPS {
+        match cpu.step() {
+            Ok(Step::Output(v)) => groups.last_mut().unwrap().push(v),
+            Ok(Step::NeedInput) if reads < MAX_READS => {
+                cpu.push_input(inputs.next().unwrap_or(0));
+                groups.push(vec![]);
+                reads += 1;
+            }
+            Ok(Step::Continue) => {}
+            _ => break,
+        }
+        if groups.iter().map(|g| g.len()).sum::<usize>() >= MAX_OUTPUTS {
+            break;
+        }
+    }
+
+    let outputs: Vec<i128> = groups.iter().flatten().copied().collect();
+    let text = outputs
+        .iter()
+        .filter(|v| **v == 10 || (32..127).contains(*v))
+        .count();
+    if outputs.contains(&10) && text * 10 >= outputs.len() * 9 {
+        return Protocol::Ascii;
+    }
+
+    let sizes: Vec<usize> = groups.iter().map(|g| g.len()).filter(|n| *n > 0).collect();
+    if sizes.len() > 1 && sizes[0] > 1 && sizes.iter().all(|n| *n == sizes[0]) {
+        return Protocol::Tuple(sizes[0]);
+    }
+    for n in [3, 2].iter().copied() {
+        // a burst cut short can end inside a group
+        let rows = outputs.len() / n;
+        if rows < 6 {
+            continue;
+        }
+        let distinct: Vec<usize> = (0..n)
+            .map(|col| {
+                let mut column: Vec<i128> = outputs[..rows * n]
+                    .iter()
+                    .skip(col)
+                    .step_by(n)
+                    .copied()
+                    .collect();
+                column.sort_unstable();
+                column.dedup();
+                column.len()
+            })
+            .collect();
+        if distinct.iter().any(|d| *d <= 5) && distinct.iter().any(|d| *d > 5) {
+            return Protocol::Tuple(n);
+        }
+    }
+    Protocol::Numeric
+}
+
+#[cfg(test)]
+mod header_tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = concat!(
+            "# name: BOOST\n",
+            "# io: tuple 3\n",
+            "# patch: mem[0]=2\n",
+            "# patch: mem[1]=-5\n",
+            "# sample: 1 -> 3063082071\n",
+            "# sample: -> 1,2\n",
+            "109,1,99\n",
+        );
+        let header = Header::parse(text).unwrap();
+        assert_eq!(Some("BOOST".to_string()), header.name);
+        assert_eq!(Some(Protocol::Tuple(3)), header.protocol);
+        assert_eq!(vec![(0, 2), (1, -5)], header.patches);
+        assert_eq!(vec![1], header.samples[0].inputs);
+        assert_eq!(Vec::<i128>::new(), header.samples[1].inputs);
+        assert_eq!(
+            text.replace("  ", " ").replace("109,1,99\n", ""),
+            header.to_text()
+        );
+
+        let mut prog = vec![109, 1, 99];
+        header.apply(&mut prog);
+        assert_eq!(vec![2, -5, 99], prog);
+
+        assert!(Header::parse("# io: binary\n").is_err());
+        assert!(Header::parse("# patch: 0=2\n").is_err());
+        assert!(Header::parse("# color: red\n").is_err());
+        assert_eq!(Header::default(), Header::parse("1,2,3\n").unwrap());
+    }
+
+    #[test]
+    fn body_lines() {
+        assert_eq!(vec![1, 2, 3, 4], body("# name: a\n1,2\n3,4\n").unwrap());
+        assert_eq!(vec![1, 2, 3, 4], body("1,2,\n3,4").unwrap());
+        assert!(body("1,2\n# io: ascii\n3,4\n").is_err());
+        assert!(body("1,2 3\n").is_err());
+    }
+
+    #[test]
+    fn shipped_inputs() {
+        let guesses = vec![
+            ("day2", vec![], Protocol::Numeric),
+            ("day5", vec![1], Protocol::Numeric),
+            ("day9", vec![1], Protocol::Numeric),
+            ("day11", vec![], Protocol::Tuple(2)),
+            ("day13", vec![], Protocol::Tuple(3)),
+            ("day15", vec![1, 2, 3, 4], Protocol::Numeric),
+            ("day17", vec![], Protocol::Ascii),
+            ("day19", vec![], Protocol::Numeric),
+            ("day21", vec![], Protocol::Ascii),
+            ("day23", vec![0, -1], Protocol::Tuple(3)),
+        ];
+        for (day, inputs, protocol) in guesses {
+            let prog = crate::cpu::parse_input(&format!("resources/{}-input.txt", day)).unwrap();
+            assert_eq!(protocol, fingerprint(&prog, &inputs), "{}", day);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+// an instruction the cpu runs, but not the way it is written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    UnknownOpcode(i128),
+    // the parameter written to is in immediate mode
+    ImmediateWrite(usize),
+    // parameter and mode digit
+    BadMode(usize, i128),
+    UnusedMode(usize, i128),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::UnknownOpcode(inst) => write!(f, "unknown opcode in '{}'", inst),
+            Problem::ImmediateWrite(n) => write!(f, "immediate mode write to parameter {}", n),
+            Problem::BadMode(n, mode) => write!(f, "mode {} on parameter {}", mode, n),
+            Problem::UnusedMode(n, mode) => write!(f, "mode {} on unused parameter {}", mode, n),
+        }
+    }
+}
+
+// parameter count and the parameter written to
+fn shape(opcode: i128) -> Option<(usize, Option<usize>)> {
+    match opcode {
+        1 | 2 | 7 | 8 => Some((3, Some(3))),
+        3 => Some((1, Some(1))),
+        4 | 9 => Some((1, None)),
+        5 | 6 => Some((2, None)),
+        99 => Some((0, None)),
+        _ => None,
+    }
+}
+
+pub fn check(inst: i128) -> Vec<Problem> {
+    let (params, written) = match shape(inst % 100) {
+        Some(shape) if inst >= 0 => shape,
+        _ => return vec![Problem::UnknownOpcode(inst)],
+    };
+
+    let mut problems = vec![];
+    let mut modes = inst / 100;
+    let mut n = 1;
+    while modes != 0 {
+        let mode = modes % 10;
+        if n > params {
+            if mode != 0 {
+                problems.push(Problem::UnusedMode(n, mode));
+            }
+        } else if mode > 2 {
+            problems.push(Problem::BadMode(n, mode));
+        } else if mode == 1 && written == Some(n) {
+            problems.push(Problem::ImmediateWrite(n));
+        }
+        modes /= 10;
+        n += 1;
+    }
+    problems
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub pc: usize,
+    pub problem: Problem,
+}
+
+// Checks every instruction reachable from 0. Both ways of a conditional
+// jump are followed unless its condition is in a cell no code writes,
+// targets read from memory end the walk. The code after
+// an unconditional jump is only walked when its address is stored by an
+// add of two immediates somewhere reached, which is how return addresses
+// are pushed. Cells written by the code are not trusted: a jump reading one
+// is followed both ways and an instruction in one isn't checked.
+#[allow(dead_code)]
+pub fn lint(prog: &[i128]) -> Vec<Lint> {
+    let mut written = BTreeSet::new();
+    loop {
+        let (lints, writes) = walk(prog, &written);
+        if writes.is_subset(&written) {
+            return lints;
+        }
+        written.extend(writes);
+    }
+}
+
+fn walk(prog: &[i128], written: &BTreeSet<usize>) -> (Vec<Lint>, BTreeSet<usize>) {
+    let mut lints = vec![];
+    let mut writes = BTreeSet::new();
+    let mut seen = BTreeSet::new();
+    let mut stored = BTreeSet::new();
+    let mut skipped = BTreeSet::new();
+    let mut todo = vec![0];
+
+    loop {
+        while let Some(pc) = todo.pop() {
+            if pc >= prog.len() || written.contains(&pc) || !seen.insert(pc) {
+                continue;
+            }
+            let inst = prog[pc];
+            let problems = check(inst);
+            lints.extend(problems.iter().map(|&problem| Lint { pc, problem }));
+            let (params, write) = match shape(inst % 100) {
+                Some(shape) if inst >= 0 => shape,
+                _ => continue,
+            };
+
+            let mode = |n: usize| inst / 10i128.pow(n as u32 + 1) % 10;
+            let param = |n: usize| {
+                if written.contains(&(pc + n)) {
+                    None
+                } else {
+                    prog.get(pc + n).copied()
+                }
+            };
+            if let Some(n) = write {
+                if let Some(addr) = param(n).filter(|a| mode(n) == 0 && *a >= 0) {
+                    writes.insert(addr as usize);
+                }
+            }
+
+            let next = pc + params + 1;
+            match inst % 100 {
+                99 => {}
+                5 | 6 => {
+                    let cond = match (mode(1), param(1)) {
+                        (1, cond) => cond.map(|c| c != 0),
+                        (0, Some(addr)) if addr >= 0 && !written.contains(&(addr as usize)) => {
+                            Some(prog.get(addr as usize).copied().unwrap_or(0) != 0)
+                        }
+                        _ => None,
+                    };
+                    let target = match mode(2) {
+                        1 => param(2),
+                        _ => None,
+                    };
+                    if let Some(target) = target.filter(|t| *t >= 0) {
+                        if cond != Some(inst % 100 == 6) {
+                            todo.push(target as usize);
+                        }
+                    }
+                    if cond == Some(inst % 100 == 6) || cond.is_none() {
+                        todo.push(next);
+                    } else {
+                        skipped.insert(next);
+                    }
+                }
+                _ => {
+                    // `1101 0 address dest` and the like
+                    if inst % 10000 == 1101 {
+                        if let (Some(a), Some(b)) = (param(1), param(2)) {
+                            stored.insert((a + b).max(0) as usize);
+                        }
+                    }
+                    todo.push(next);
+                }
+            }
+        }
+
+        todo = skipped
+            .iter()
+            .filter(|pc| stored.contains(pc) && !seen.contains(pc))
+            .copied()
+            .collect();
+        if todo.is_empty() {
+            break;
+        }
+    }
+
+    lints.sort_by_key(|lint| lint.pc);
+    (lints, writes)
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use super::*;
+
+    #[test]
+    fn problems() {
+        assert!(check(21101).is_empty());
+        assert!(check(99).is_empty());
+        assert_eq!(vec![Problem::ImmediateWrite(3)], check(11101));
+        assert_eq!(vec![Problem::ImmediateWrite(1)], check(103));
+        assert_eq!(vec![Problem::BadMode(2, 3)], check(3002));
+        assert_eq!(
+            vec![Problem::UnusedMode(2, 1), Problem::UnusedMode(3, 2)],
+            check(21104)
+        );
+        assert_eq!(vec![Problem::UnusedMode(6, 1)], check(10000099));
+        assert_eq!(vec![Problem::UnknownOpcode(-1)], check(-1));
+        assert_eq!("mode 3 on parameter 2", Problem::BadMode(2, 3).to_string());
+    }
+
+    #[test]
+    fn self_modifying() {
+        // 0: turns 8 into a jump to 12, 4: jumps to 8 with a target it wrote
+        let prog = vec![1101, 1100, 5, 8, 1105, 1, 8, 99, 0, 1, 12, 99, 99];
+        assert!(lint(&prog).is_empty());
+    }
+
+    #[test]
+    fn walks_code() {
+        // 0: push 7 as a return address, 4: jump to 10, 7: halt, 8: data
+        // 10: bad output, 12: return through the pushed address
+        let prog = vec![
+            1101, 7, 0, 20, 1105, 1, 10, 99, 3333, 3333, 1004, 20, 106, 0, 20,
+        ];
+        let lints = lint(&prog);
+        assert_eq!(
+            vec![Lint {
+                pc: 10,
+                problem: Problem::UnusedMode(2, 1),
+            }],
+            lints
+        );
+    }
+
+    #[test]
+    fn shipped_inputs() {
+        for day in [2, 5, 7, 9, 11, 13, 15, 17, 19, 21, 23].iter() {
+            let prog = crate::cpu::parse_input(&format!("resources/day{}-input.txt", day)).unwrap();
+            assert_eq!(Vec::<Lint>::new(), lint(&prog), "day {}", day);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+const PAGE_SIZE: u128 = 256;
+
+// An immutable program that any number of cpus can share. Cloning only
+// bumps a reference count.
+#[derive(Debug, Clone)]
+pub struct Image(Arc<Vec<i128>>);
+
+impl Image {
+    pub fn new(program: &[i128]) -> Image {
+        Image(Arc::new(program.to_vec()))
+    }
+
+    pub fn get(&self, k: u128) -> i128 {
+        if k < self.0.len() as u128 {
+            self.0[k as usize]
+        } else {
+            0
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn page(&self, n: u128) -> Vec<i128> {
+        let start = n * PAGE_SIZE;
+        (0..PAGE_SIZE).map(|k| self.get(start + k)).collect()
+    }
+}
+
+impl From<&[i128]> for Image {
+    fn from(program: &[i128]) -> Image {
+        Image::new(program)
+    }
+}
+
+impl From<&Vec<i128>> for Image {
+    fn from(program: &Vec<i128>) -> Image {
+        Image::new(program)
+    }
+}
+
+impl From<&Image> for Image {
+    fn from(image: &Image) -> Image {
+        image.clone()
+    }
+}
+
+// Reads go through to the shared image until a page is written to, the
+// first write copies the page.
+#[derive(Debug, Clone)]
+pub struct Memory {
+    image: Image,
+    pages: HashMap<u128, Vec<i128>>,
+}
+
+impl Memory {
+    pub fn new(image: Image) -> Memory {
+        Memory {
+            image,
+            pages: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, k: u128) -> i128 {
+        match self.pages.get(&(k / PAGE_SIZE)) {
+            Some(page) => page[(k % PAGE_SIZE) as usize],
+            None => self.image.get(k),
+        }
+    }
+
+    pub fn set(&mut self, k: u128, v: i128) {
+        let image = &self.image;
+        let page = self
+            .pages
+            .entry(k / PAGE_SIZE)
+            .or_insert_with(|| image.page(k / PAGE_SIZE));
+        page[(k % PAGE_SIZE) as usize] = v;
+    }
+
+    // drops every copied page, bringing back the pristine image
+    pub fn reset(&mut self) {
+        self.pages.clear();
+    }
+
+    // every cell of the image and of the copied pages
+    #[allow(dead_code)]
+    pub fn snapshot(&self) -> BTreeMap<u128, i128> {
+        let mut cells: BTreeMap<u128, i128> = (0..self.image.len() as u128)
+            .map(|k| (k, self.get(k)))
+            .collect();
+        for (n, page) in self.pages.iter() {
+            let start = n * PAGE_SIZE;
+            cells.extend(
+                page.iter()
+                    .enumerate()
+                    .map(|(k, v)| (start + k as u128, *v)),
+            );
+        }
+        cells
+    }
+
+    #[allow(dead_code)]
+    pub fn copied_pages(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+
+    #[test]
+    fn copy_on_write() {
+        let image = Image::new(&[1, 2, 3]);
+        let mut a = Memory::new(image.clone());
+        let b = Memory::new(image.clone());
+
+        a.set(1, 42);
+        a.set(1000, 7);
+
+        assert_eq!(42, a.get(1));
+        assert_eq!(3, a.get(2));
+        assert_eq!(7, a.get(1000));
+        assert_eq!(0, a.get(999));
+        assert_eq!(2, a.copied_pages());
+
+        assert_eq!(2, b.get(1));
+        assert_eq!(0, b.get(1000));
+        assert_eq!(0, b.copied_pages());
+        assert_eq!(2, image.get(1));
+
+        let cells = a.snapshot();
+        assert_eq!(2 * PAGE_SIZE as usize, cells.len());
+        assert_eq!(Some(&42), cells.get(&1));
+        assert_eq!(Some(&7), cells.get(&1000));
+        assert_eq!(3, b.snapshot().len());
+    }
+
+    #[test]
+    fn reset() {
+        let mut mem = Memory::new(Image::new(&[1, 2, 3]));
+        mem.set(0, 5);
+        mem.set(u128::MAX, 9);
+        assert_eq!(9, mem.get(u128::MAX));
+
+        mem.reset();
+        assert_eq!(1, mem.get(0));
+        assert_eq!(0, mem.get(u128::MAX));
+        assert_eq!(0, mem.copied_pages());
+    }
+}
@@ -0,0 +1,1023 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+pub mod calls;
+pub mod corpus;
+pub mod coverage;
+pub mod debug;
+pub mod decoder;
+pub mod disasm;
+pub mod dump;
+pub mod gdb;
+pub mod header;
+pub mod lint;
+mod memory;
+pub mod protect;
+pub mod record;
+pub mod scan;
+pub mod taint;
+use calls::CallGraph;
+use coverage::Coverage;
+pub use memory::Image;
+use memory::Memory;
+use protect::{Access, Modification, Protection};
+use record::{Event, Recording};
+use taint::Taint;
+
+// the program in a file, skipping its header. see `header::load` for the
+// header too
+#[allow(dead_code)]
+pub fn parse_input(fname: &str) -> Result<Vec<i128>> {
+    Ok(header::load(fname)?.1)
+}
+
+// runs the program to completion without threads or channels
+#[allow(dead_code)]
+pub fn run_with_inputs(program: impl Into<Image>, inputs: &[i128]) -> Result<Vec<i128>> {
+    outputs(program, inputs.iter().copied()).collect()
+}
+
+// lazily runs the program, pulling a value from `inputs` whenever it asks for one
+#[allow(dead_code)]
+pub fn outputs<I>(program: impl Into<Image>, inputs: I) -> Outputs<I::IntoIter>
+where
+    I: IntoIterator<Item = i128>,
+{
+    Cpu::new_detached(program).into_outputs(inputs)
+}
+
+pub struct Outputs<I> {
+    cpu: Cpu,
+    inputs: I,
+    done: bool,
+}
+
+impl<I> Outputs<I> {
+    // the cpu as it was left by the last output, halt or error
+    #[allow(dead_code)]
+    pub fn into_cpu(self) -> Cpu {
+        self.cpu
+    }
+}
+
+impl<I: Iterator<Item = i128>> Iterator for Outputs<I> {
+    type Item = Result<i128>;
+
+    fn next(&mut self) -> Option<Result<i128>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.cpu.step() {
+                Ok(Step::Continue) => {}
+                Ok(Step::Output(val)) => return Some(Ok(val)),
+                Ok(Step::NeedInput) => match self.inputs.next() {
+                    Some(val) => self.cpu.push_input(val),
+                    None => {
+                        self.done = true;
+                        return Some(Err(anyhow::anyhow!("out of inputs at '{}'", self.cpu.pc)));
+                    }
+                },
+                Ok(Step::Halt) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+// the opcode and the modes of its three parameters
+type Instruction = (i128, i128, i128, i128);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    Continue,
+    Output(i128),
+    NeedInput,
+    Halt,
+}
+
+pub struct Cpu {
+    pc: u128,
+    base: i128,
+    sender: Option<Sender<i128>>,
+    recver: Option<Receiver<i128>>,
+    inputs: VecDeque<i128>,
+    mem: Memory,
+    taint: Option<Taint>,
+    coverage: Option<Coverage>,
+    strict: bool,
+    pins: BTreeMap<u128, i128>,
+    count: u64,
+    recording: Option<Recording>,
+    calls: Option<CallGraph>,
+    protection: Protection,
+    // the cell selecting the port of inputs and outputs, the port it
+    // selected last and the inputs of the other ports
+    port_register: Option<u128>,
+    port: i128,
+    queues: BTreeMap<i128, VecDeque<i128>>,
+}
+
+impl Cpu {
+    #[allow(dead_code)]
+    pub fn new(program: impl Into<Image>) -> (Cpu, Sender<i128>, Receiver<i128>) {
+        let (tx, recver): (Sender<i128>, Receiver<i128>) = channel();
+        let (sender, rx): (Sender<i128>, Receiver<i128>) = channel();
+
+        (Cpu::new_with_send_recv(program, sender, recver), tx, rx)
+    }
+
+    pub fn new_with_send_recv(
+        program: impl Into<Image>,
+        sender: Sender<i128>,
+        recver: Receiver<i128>,
+    ) -> Cpu {
+        let mut cpu = Cpu::new_detached(program);
+        cpu.sender = Some(sender);
+        cpu.recver = Some(recver);
+        cpu
+    }
+
+    // a cpu without channels, driven with `step` and `push_input`
+    // cpus made from the same image share it until they write to it
+    pub fn new_detached(program: impl Into<Image>) -> Cpu {
+        Cpu {
+            pc: 0u128,
+            base: 0,
+            sender: None,
+            recver: None,
+            inputs: VecDeque::new(),
+            mem: Memory::new(program.into()),
+            taint: None,
+            coverage: None,
+            strict: false,
+            pins: BTreeMap::new(),
+            count: 0,
+            recording: None,
+            calls: None,
+            protection: Protection::default(),
+            port_register: None,
+            port: 0,
+            queues: BTreeMap::new(),
+        }
+    }
+
+    // back to the pristine image, pending inputs are dropped. coverage is
+    // kept and adds up over the runs, pinned cells stay pinned
+    #[allow(dead_code)]
+    pub fn reset(&mut self) {
+        self.pc = 0;
+        self.base = 0;
+        self.inputs.clear();
+        self.queues.clear();
+        self.port = 0;
+        self.mem.reset();
+        for (k, v) in self.pins.iter() {
+            self.mem.set(*k, *v);
+        }
+        self.count = 0;
+        if self.taint.is_some() {
+            self.taint = Some(Taint::default());
+        }
+        if self.recording.is_some() {
+            self.recording = Some(Recording::default());
+        }
+        if self.calls.is_some() {
+            self.calls = Some(CallGraph::default());
+        }
+        self.protection.reset();
+    }
+
+    // tags every input with its index and follows the tags through memory,
+    // outputs and jumps
+    #[allow(dead_code)]
+    pub fn track_taint(&mut self) {
+        self.taint = Some(Taint::default());
+    }
+
+    #[allow(dead_code)]
+    pub fn taint_report(&self) -> Option<taint::Report> {
+        self.taint.as_ref().map(|taint| taint.report())
+    }
+
+    // counts the instructions that run and the ways jumps and comparisons go
+    #[allow(dead_code)]
+    pub fn track_coverage(&mut self) {
+        self.coverage = Some(Coverage::default());
+    }
+
+    #[allow(dead_code)]
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    // logs every input and output with the instruction count it happened at
+    #[allow(dead_code)]
+    pub fn record(&mut self) {
+        self.recording = Some(Recording::default());
+    }
+
+    #[allow(dead_code)]
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    // follows calls and returns to recover functions and the call stack,
+    // see `calls::CallGraph`
+    #[allow(dead_code)]
+    pub fn track_calls(&mut self) {
+        self.calls = Some(CallGraph::default());
+    }
+
+    #[allow(dead_code)]
+    pub fn calls(&self) -> Option<&CallGraph> {
+        self.calls.as_ref()
+    }
+
+    // stops the cpu with an error when it breaks the rules of `access`
+    // anywhere in `start..end`
+    #[allow(dead_code)]
+    pub fn protect(&mut self, start: u128, end: u128, access: Access) {
+        self.protection
+            .regions
+            .push(protect::Region { start, end, access });
+    }
+
+    // logs every write to a cell of an instruction that already ran
+    #[allow(dead_code)]
+    pub fn report_self_modification(&mut self) {
+        self.protection.report();
+    }
+
+    #[allow(dead_code)]
+    pub fn self_modifications(&self) -> Option<&[Modification]> {
+        if self.protection.reporting() {
+            Some(&self.protection.log)
+        } else {
+            None
+        }
+    }
+
+    // instructions executed so far
+    #[allow(dead_code)]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    #[allow(dead_code)]
+    pub fn pc(&self) -> u128 {
+        self.pc
+    }
+
+    #[allow(dead_code)]
+    pub fn base(&self) -> i128 {
+        self.base
+    }
+
+    // errors on instructions with modes the cpu would otherwise ignore or
+    // read as position mode, see `lint::check`
+    #[allow(dead_code)]
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    #[allow(dead_code)]
+    pub fn into_outputs<I>(self, inputs: I) -> Outputs<I::IntoIter>
+    where
+        I: IntoIterator<Item = i128>,
+    {
+        Outputs {
+            cpu: self,
+            inputs: inputs.into_iter(),
+            done: false,
+        }
+    }
+
+    // queues an input for the selected port
+    pub fn push_input(&mut self, v: i128) {
+        self.inputs.push_back(v);
+    }
+
+    // Maps the port register to `addr`. Every input and output goes to the
+    // port numbered by the value of the cell, which starts out as port 0
+    // when the program doesn't set it. Each port has its own inputs.
+    #[allow(dead_code)]
+    pub fn map_ports(&mut self, addr: u128) {
+        self.port_register = Some(addr);
+    }
+
+    // the port the next input or output goes to
+    #[allow(dead_code)]
+    pub fn port(&self) -> i128 {
+        self.port
+    }
+
+    #[allow(dead_code)]
+    pub fn push_port_input(&mut self, port: i128, v: i128) {
+        if port == self.port {
+            self.inputs.push_back(v);
+        } else {
+            self.queues.entry(port).or_default().push_back(v);
+        }
+    }
+
+    // swaps in the inputs of the port the register selects
+    fn select_port(&mut self) {
+        let port = match self.port_register {
+            Some(addr) => self.mem.get(addr),
+            None => return,
+        };
+        if port != self.port {
+            let inputs = self.queues.remove(&port).unwrap_or_default();
+            let inputs = std::mem::replace(&mut self.inputs, inputs);
+            if !inputs.is_empty() {
+                self.queues.insert(self.port, inputs);
+            }
+            self.port = port;
+        }
+    }
+
+    pub fn get_mem(&self, k: u128) -> Result<i128> {
+        Ok(self.mem.get(k))
+    }
+
+    pub fn set_mem(&mut self, k: u128, v: i128) {
+        self.mem.set(k, v);
+    }
+
+    // keeps a cell at `v`, whatever the program writes to it
+    #[allow(dead_code)]
+    pub fn pin(&mut self, k: u128, v: i128) {
+        self.pins.insert(k, v);
+        self.set_mem(k, v);
+    }
+
+    #[allow(dead_code)]
+    pub fn unpin(&mut self, k: u128) {
+        self.pins.remove(&k);
+    }
+
+    fn parse_instruction(&self) -> Result<Instruction> {
+        let s = format!("{}{}", "0000", self.get_mem(self.pc)?);
+        let inst: Vec<char> = s.chars().rev().take(5).collect();
+
+        let opstr = format!("{}{}", inst[1], inst[0]);
+        let opcode: i128 = opstr.parse()?;
+        let m1: i128 = inst[2] as i128 - 48;
+        let m2: i128 = inst[3] as i128 - 48;
+        let m3: i128 = inst[4] as i128 - 48;
+        Ok((opcode, m1, m2, m3))
+    }
+
+    fn get_param(&self, offset: i128, mode: u128) -> Result<i128> {
+        match mode {
+            0 => Ok(self.get_mem(self.get_mem((self.pc as i128 + offset) as u128)? as u128)?),
+            1 => Ok(self.get_mem((self.pc as i128 + offset) as u128)?),
+            2 => Ok(self.get_mem(
+                (self.base + self.get_mem((self.pc as i128 + offset) as u128)?) as u128,
+            )?),
+            _ => anyhow::bail!("unknown mode: {}", mode),
+        }
+    }
+
+    // the address a parameter refers to, `None` for immediates
+    fn param_addr(&self, offset: u128, mode: i128) -> Result<Option<u128>> {
+        let v = self.get_mem(self.pc + offset)?;
+        match mode {
+            0 => Ok(Some(v as u128)),
+            2 => Ok(Some((self.base + v) as u128)),
+            _ => Ok(None),
+        }
+    }
+
+    // the address a write parameter lands on, writes in immediate mode go
+    // where position mode would
+    fn write_addr(&self, offset: u128, mode: i128) -> Result<u128> {
+        let v = self.get_mem(self.pc + offset)?;
+        match mode {
+            2 => Ok((self.base + v) as u128),
+            _ => Ok(v as u128),
+        }
+    }
+
+    // records the instruction about to execute
+    fn cover(&mut self, (opcode, m1, m2, _): Instruction) -> Result<()> {
+        if self.coverage.is_none() {
+            return Ok(());
+        }
+
+        let branch = match opcode {
+            3 if self.inputs.is_empty() => return Ok(()),
+            5 => Some(self.get_param(1, m1 as u128)? != 0),
+            6 => Some(self.get_param(1, m1 as u128)? == 0),
+            7 | 8 => {
+                let (a, b) = (
+                    self.get_param(1, m1 as u128)?,
+                    self.get_param(2, m2 as u128)?,
+                );
+                Some(if opcode == 7 { a < b } else { a == b })
+            }
+            _ => None,
+        };
+        let pc = self.pc;
+        self.coverage.as_mut().unwrap().hit(pc, branch);
+        Ok(())
+    }
+
+    // counts the instruction about to execute and records its input or output
+    fn log(&mut self, (opcode, m1, _, _): Instruction) -> Result<()> {
+        if opcode == 3 && self.inputs.is_empty() {
+            return Ok(());
+        }
+        let count = self.count;
+        self.count += 1;
+        if self.recording.is_none() {
+            return Ok(());
+        }
+
+        let event = match opcode {
+            3 => Event::Input(self.inputs[0]),
+            4 => Event::Output(self.get_param(1, m1 as u128)?),
+            _ => return Ok(()),
+        };
+        self.recording.as_mut().unwrap().events.push((count, event));
+        Ok(())
+    }
+
+    // checks the instruction about to execute against the protected regions
+    // and logs its write when it lands on code that already ran
+    fn guard(&mut self, (opcode, m1, m2, m3): Instruction) -> Result<()> {
+        if self.protection.is_empty() {
+            return Ok(());
+        }
+
+        if opcode == 3 && self.inputs.is_empty() {
+            return Ok(());
+        }
+        let pc = self.pc;
+        if let Some(region) = self.protection.find(pc, Access::NoExecute) {
+            anyhow::bail!("execution in {} at '{}'", region, pc);
+        }
+
+        let (len, reads, write) = match opcode {
+            1 | 2 | 7 | 8 => (4, vec![(1, m1), (2, m2)], Some((3, m3))),
+            3 => (2, vec![], Some((1, m1))),
+            4 | 9 => (2, vec![(1, m1)], None),
+            5 | 6 => (3, vec![(1, m1), (2, m2)], None),
+            _ => (1, vec![], None),
+        };
+        for (n, mode) in reads {
+            if let Some(addr) = self.param_addr(n, mode)? {
+                if let Some(region) = self.protection.find(addr, Access::ExecuteOnly) {
+                    anyhow::bail!("read of {} in {} at '{}'", addr, region, pc);
+                }
+            }
+        }
+        self.protection.run(pc, len);
+
+        let addr = match write {
+            Some((n, mode)) => self.write_addr(n, mode)?,
+            None => return Ok(()),
+        };
+        let region = self
+            .protection
+            .find(addr, Access::ReadOnly)
+            .or_else(|| self.protection.find(addr, Access::ExecuteOnly));
+        if let Some(region) = region {
+            anyhow::bail!("write to {} in {} at '{}'", addr, region, pc);
+        }
+        if self.protection.executed(addr) {
+            let new = match opcode {
+                3 => self.inputs[0],
+                _ => {
+                    let (a, b) = (
+                        self.get_param(1, m1 as u128)?,
+                        self.get_param(2, m2 as u128)?,
+                    );
+                    match opcode {
+                        1 => a + b,
+                        2 => a * b,
+                        7 => (a < b) as i128,
+                        _ => (a == b) as i128,
+                    }
+                }
+            };
+            self.protection.log.push(Modification {
+                count: self.count,
+                pc,
+                addr,
+                old: self.mem.get(addr),
+                new,
+            });
+        }
+        Ok(())
+    }
+
+    // follows the calls and returns of the instruction about to execute
+    fn follow(&mut self, (opcode, m1, m2, m3): Instruction) -> Result<()> {
+        if self.calls.is_none() {
+            return Ok(());
+        }
+
+        let dest = match opcode {
+            1 | 2 | 7 | 8 => Some(self.write_addr(3, m3)?),
+            3 if !self.inputs.is_empty() => Some(self.write_addr(1, m1)?),
+            _ => None,
+        };
+        let target = match opcode {
+            5 | 6 if (self.get_param(1, m1 as u128)? != 0) == (opcode == 5) => {
+                Some(self.get_param(2, m2 as u128)? as u128)
+            }
+            _ => None,
+        };
+
+        let calls = self.calls.as_mut().unwrap();
+        if let Some(target) = target {
+            calls.jump(&self.mem, self.pc, target, self.base);
+        }
+        if let Some(dest) = dest {
+            calls.write(dest);
+        }
+        Ok(())
+    }
+
+    // propagates taint for the instruction about to execute
+    fn trace(&mut self, (opcode, m1, m2, m3): Instruction) -> Result<()> {
+        if self.taint.is_none() {
+            return Ok(());
+        }
+
+        let (a1, a2) = (self.param_addr(1, m1)?, self.param_addr(2, m2)?);
+        let pc = self.pc;
+        let first = match opcode {
+            3 => match self.inputs.front() {
+                Some(val) => *val,
+                None => return Ok(()),
+            },
+            4..=6 => self.get_param(1, m1 as u128)?,
+            _ => 0,
+        };
+        let dest = match opcode {
+            1 | 2 | 7 | 8 => Some(self.write_addr(3, m3)?),
+            3 => Some(self.write_addr(1, m1)?),
+            _ => None,
+        };
+
+        let taint = self.taint.as_mut().unwrap();
+        let tags = taint.tags(a1);
+        match (opcode, dest) {
+            (1, Some(dest)) | (2, Some(dest)) | (7, Some(dest)) | (8, Some(dest)) => {
+                let tags = tags.union(&taint.tags(a2)).cloned().collect();
+                taint.write(dest, tags);
+            }
+            (3, Some(dest)) => taint.input(dest, first),
+            (4, _) => taint.output(first, tags),
+            (5, _) | (6, _) => {
+                let taken = (first != 0) == (opcode == 5);
+                let tags = tags.union(&taint.tags(a2)).cloned().collect();
+                taint.jump(pc, taken, tags);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn execute(&mut self) -> Result<()> {
+        loop {
+            match self.step()? {
+                Step::Continue => {}
+                Step::Output(val) => match &self.sender {
+                    Some(sender) => sender.send(val)?,
+                    None => anyhow::bail!("no output channel for '{}'", val),
+                },
+                Step::NeedInput => match &self.recver {
+                    Some(recver) => self.inputs.push_back(recver.recv()?),
+                    None => anyhow::bail!("no input available at '{}'", &self.pc),
+                },
+                Step::Halt => break,
+            }
+        }
+        Ok(())
+    }
+
+    // executes a single instruction. an input instruction without a pushed
+    // input is left unexecuted and reported as `Step::NeedInput`
+    pub fn step(&mut self) -> Result<Step> {
+        for (k, v) in self.pins.iter() {
+            self.mem.set(*k, *v);
+        }
+        self.select_port();
+        if self.strict {
+            let inst = self.get_mem(self.pc)?;
+            if let Some(problem) = lint::check(inst).first() {
+                anyhow::bail!("{} at '{}'", problem, self.pc);
+            }
+        }
+        let inst = self.parse_instruction()?;
+        self.guard(inst)?;
+        self.trace(inst)?;
+        self.cover(inst)?;
+        self.follow(inst)?;
+        self.log(inst)?;
+
+        match inst {
+            (1, m1, m2, m3) => {
+                let c = self.write_addr(3, m3)?;
+                self.set_mem(
+                    c,
+                    self.get_param(1, m1 as u128)? + self.get_param(2, m2 as u128)?,
+                );
+                self.pc += 4;
+            }
+            (2, m1, m2, m3) => {
+                let c = self.write_addr(3, m3)?;
+                self.set_mem(
+                    c,
+                    self.get_param(1, m1 as u128)? * self.get_param(2, m2 as u128)?,
+                );
+                self.pc += 4;
+            }
+            (3, m1, _, _) => {
+                let a = self.write_addr(1, m1)?;
+                let val = match self.inputs.pop_front() {
+                    Some(val) => val,
+                    None => return Ok(Step::NeedInput),
+                };
+                self.set_mem(a, val);
+                self.pc += 2;
+            }
+            (4, m1, _, _) => {
+                let a = self.get_param(1, m1 as u128)?;
+
+                self.pc += 2;
+                return Ok(Step::Output(a));
+            }
+            (5, m1, m2, _) => {
+                let a = self.get_param(1, m1 as u128)?;
+                let b = self.get_param(2, m2 as u128)?;
+                if a != 0 {
+                    self.pc = b as u128;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            (6, m1, m2, _) => {
+                let a = self.get_param(1, m1 as u128)?;
+                let b = self.get_param(2, m2 as u128)?;
+                if a == 0 {
+                    self.pc = b as u128;
+                } else {
+                    self.pc += 3;
+                }
+            }
+            (7, m1, m2, m3) => {
+                let a = self.get_param(1, m1 as u128)?;
+                let b = self.get_param(2, m2 as u128)?;
+
+                let c = self.write_addr(3, m3)?;
+                if a < b {
+                    self.set_mem(c, 1);
+                } else {
+                    self.set_mem(c, 0);
+                }
+                self.pc += 4;
+            }
+            (8, m1, m2, m3) => {
+                let a = self.get_param(1, m1 as u128)?;
+                let b = self.get_param(2, m2 as u128)?;
+                let c = self.write_addr(3, m3)?;
+                if a == b {
+                    self.set_mem(c, 1);
+                } else {
+                    self.set_mem(c, 0);
+                }
+                self.pc += 4;
+            }
+            (9, m1, _, _) => {
+                let a = self.get_param(1, m1 as u128)?;
+                self.base = self.base as i128 + a;
+                self.pc += 2;
+            }
+            (99, _, _, _) => return Ok(Step::Halt),
+            _ => anyhow::bail!(
+                "unknown opcode '{}' at '{}'",
+                self.get_mem(self.pc)?,
+                &self.pc
+            ),
+        }
+        Ok(Step::Continue)
+    }
+}
+
+#[cfg(test)]
+mod cpu_tests {
+    use super::*;
+
+    #[test]
+    fn conformance() {
+        let cases = corpus::load("resources/intcode-corpus.txt").unwrap();
+        let failures = corpus::failures(&cases, |prog, run| {
+            let mut out = Cpu::new_detached(prog).into_outputs(run.inputs.clone());
+            let mut outcome = corpus::Outcome::default();
+            for v in out.by_ref() {
+                match v {
+                    Ok(v) => outcome.outputs.push(v),
+                    Err(e) => outcome.error = Some(e.to_string()),
+                }
+            }
+            let len = run.memory.as_ref().map_or(0, |m| m.len());
+            let cpu = out.into_cpu();
+            outcome.memory = (0..len as u128).map(|k| cpu.get_mem(k).unwrap()).collect();
+            outcome
+        });
+        assert!(failures.is_empty(), "{:#?}", failures);
+    }
+
+    #[test]
+    fn ports() {
+        // outputs 65 on port 1 and 66 on port 2, then copies an input from
+        // port 3 to port 1 and one from port 0 to port 2
+        let prog = vec![
+            1101, 0, 1, 100, 104, 65, 1101, 0, 2, 100, 104, 66, 1101, 0, 3, 100, 3, 101, 1101, 0,
+            1, 100, 4, 101, 1101, 0, 0, 100, 3, 101, 1101, 0, 2, 100, 4, 101, 99,
+        ];
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.map_ports(100);
+        cpu.push_input(5);
+        cpu.push_port_input(3, 7);
+
+        let mut io = vec![];
+        loop {
+            match cpu.step().unwrap() {
+                Step::Output(v) => io.push((cpu.port(), v)),
+                Step::Halt => break,
+                Step::NeedInput => panic!("port {} ran dry", cpu.port()),
+                Step::Continue => {}
+            }
+        }
+        assert_eq!(vec![(1, 65), (2, 66), (1, 7), (2, 5)], io);
+
+        // without the register everything is port 0
+        let mut out = Cpu::new_detached(&prog).into_outputs(vec![7, 5]);
+        let out: Vec<i128> = out.by_ref().map(|v| v.unwrap()).collect();
+        assert_eq!(vec![65, 66, 7, 5], out);
+    }
+
+    #[test]
+    fn parse_instruction() {
+        let (tx, rx): (Sender<i128>, Receiver<i128>) = channel();
+        let cpu = Cpu::new_with_send_recv(&vec![1002], tx, rx);
+        assert_eq!(cpu.parse_instruction().unwrap(), (2, 0, 1, 0));
+    }
+
+    #[test]
+    fn compare_tests() {
+        let progs = vec![
+            vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8],
+            vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8],
+            vec![3, 3, 1108, -1, 8, 3, 4, 3, 99],
+            vec![3, 3, 1107, -1, 8, 3, 4, 3, 99],
+        ];
+
+        for prog in progs {
+            let (tx, rx): (Sender<i128>, Receiver<i128>) = channel();
+            let (tx2, rx2): (Sender<i128>, Receiver<i128>) = channel();
+            let mut cpu = Cpu::new_with_send_recv(&prog, tx2, rx);
+            tx.send(9).unwrap();
+            cpu.execute().unwrap();
+            assert_eq!(0, rx2.recv().unwrap());
+        }
+    }
+
+    #[test]
+    fn jump_tests() {
+        let progs = vec![
+            vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9],
+            vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1],
+        ];
+
+        for prog in progs {
+            let (tx, rx): (Sender<i128>, Receiver<i128>) = channel();
+            let (tx2, rx2): (Sender<i128>, Receiver<i128>) = channel();
+            let mut cpu = Cpu::new_with_send_recv(&prog, tx2, rx);
+            tx.send(0).unwrap();
+            cpu.execute().unwrap();
+            assert_eq!(0, rx2.recv().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_larger_program() {
+        let prog = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+
+        let (tx, rx): (Sender<i128>, Receiver<i128>) = channel();
+        let (tx2, rx2): (Sender<i128>, Receiver<i128>) = channel();
+        let mut cpu = Cpu::new_with_send_recv(&prog, tx2, rx);
+        tx.send(7).unwrap();
+        cpu.execute().unwrap();
+        assert_eq!(999, rx2.recv().unwrap());
+    }
+
+    #[test]
+    fn relative_base() {
+        let prog: Vec<i128> = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+
+        let (_, rx): (Sender<i128>, Receiver<i128>) = channel();
+        let (tx2, rx2): (Sender<i128>, Receiver<i128>) = channel();
+        let mut cpu = Cpu::new_with_send_recv(&prog, tx2, rx);
+
+        cpu.execute().unwrap();
+
+        let mut output = vec![];
+        for _ in 0..prog.len() {
+            match rx2.recv() {
+                Ok(val) => output.push(val),
+                Err(_) => break,
+            }
+        }
+        assert_eq!(&prog, &output);
+    }
+
+    #[test]
+    fn relative_base2() {
+        let prog: Vec<i128> = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+
+        let (_, rx): (Sender<i128>, Receiver<i128>) = channel();
+        let (tx2, rx2): (Sender<i128>, Receiver<i128>) = channel();
+        let mut cpu = Cpu::new_with_send_recv(&prog, tx2, rx);
+
+        cpu.execute().unwrap();
+        let output = rx2.recv().unwrap();
+        let digit_cnt = output.to_string().chars().count();
+        assert_eq!(16, digit_cnt);
+    }
+
+    #[test]
+    fn relative_base3() {
+        let prog: Vec<i128> = vec![104, 1125899906842624, 99];
+
+        let (_, rx): (Sender<i128>, Receiver<i128>) = channel();
+        let (tx2, rx2): (Sender<i128>, Receiver<i128>) = channel();
+        let mut cpu = Cpu::new_with_send_recv(&prog, tx2, rx);
+
+        cpu.execute().unwrap();
+        let output = rx2.recv().unwrap();
+        assert_eq!(1125899906842624, output);
+    }
+
+    #[test]
+    fn step_and_push_input() {
+        let prog = vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+        let mut cpu = Cpu::new_detached(&prog);
+
+        assert_eq!(Step::NeedInput, cpu.step().unwrap());
+        assert_eq!(Step::NeedInput, cpu.step().unwrap());
+        cpu.push_input(41);
+        assert_eq!(Step::Continue, cpu.step().unwrap());
+        assert_eq!(Step::Continue, cpu.step().unwrap());
+        assert_eq!(Step::Output(42), cpu.step().unwrap());
+        assert_eq!(Step::Halt, cpu.step().unwrap());
+        assert_eq!(Step::Halt, cpu.step().unwrap());
+    }
+
+    #[test]
+    fn run_with_inputs_test() {
+        let prog = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        assert_eq!(vec![1], run_with_inputs(&prog, &[8]).unwrap());
+        assert_eq!(vec![0], run_with_inputs(&prog, &[9]).unwrap());
+        assert!(run_with_inputs(&prog, &[]).is_err());
+    }
+
+    #[test]
+    fn lazy_outputs() {
+        // echoes its input forever
+        let prog = vec![3, 100, 4, 100, 1105, 1, 0];
+
+        let mut out = outputs(&prog, vec![1, 2, 3]);
+        assert_eq!(1, out.next().unwrap().unwrap());
+        assert_eq!(2, out.next().unwrap().unwrap());
+        assert_eq!(3, out.next().unwrap().unwrap());
+        assert!(out.next().unwrap().is_err());
+        assert!(out.next().is_none());
+
+        let doubled: Vec<i128> = outputs(&prog, (1..).map(|n| n * 2))
+            .take(4)
+            .map(|val| val.unwrap())
+            .collect();
+        assert_eq!(vec![2, 4, 6, 8], doubled);
+    }
+
+    #[test]
+    fn shared_image_and_reset() {
+        let image = Image::new(&[1001, 5, 1, 5, 99, 10]);
+        let mut a = Cpu::new_detached(&image);
+        let b = Cpu::new_detached(&image);
+
+        a.execute().unwrap();
+        assert_eq!(11, a.get_mem(5).unwrap());
+        assert_eq!(10, b.get_mem(5).unwrap());
+
+        a.reset();
+        assert_eq!(10, a.get_mem(5).unwrap());
+        a.execute().unwrap();
+        assert_eq!(11, a.get_mem(5).unwrap());
+    }
+
+    #[test]
+    fn strict_modes() {
+        // the immediate write lands in 5 like a position mode one
+        let prog = vec![11101, 2, 3, 5, 4, 0, 99];
+        assert_eq!(vec![5], run_with_inputs(&prog, &[]).unwrap());
+
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.set_strict(true);
+        let err = cpu.step().unwrap_err();
+        assert_eq!(
+            "immediate mode write to parameter 3 at '0'",
+            err.to_string()
+        );
+
+        let mut cpu = Cpu::new_detached(&vec![1104, 7, 99]);
+        cpu.set_strict(true);
+        assert!(cpu.step().is_err());
+        cpu.set_strict(false);
+        assert_eq!(Step::Output(7), cpu.step().unwrap());
+    }
+
+    #[test]
+    fn taint_tracking() {
+        // outputs in0 * 3 and in1, branches on in1 < 5, then outputs 7
+        let prog = vec![
+            3, 40, 3, 41, 1002, 40, 3, 42, 4, 42, 4, 41, 1007, 41, 5, 43, 1005, 43, 21, 104, 7, 99,
+        ];
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.track_taint();
+
+        let mut out = cpu.into_outputs(vec![2, 9]);
+        let values = out.by_ref().collect::<Result<Vec<i128>>>().unwrap();
+        assert_eq!(vec![6, 9, 7], values);
+
+        let report = out.into_cpu().taint_report().unwrap();
+        let tags: Vec<Vec<usize>> = report
+            .outputs
+            .iter()
+            .map(|o| o.tags.iter().copied().collect())
+            .collect();
+        assert_eq!(vec![vec![0], vec![1], vec![]], tags);
+        assert_eq!(1, report.jumps.len());
+        assert_eq!(
+            concat!(
+                r#"{"inputs":[2,9],"outputs":[{"value":6,"tags":[0]},{"value":9,"tags":[1]},"#,
+                r#"{"value":7,"tags":[]}],"jumps":[{"pc":16,"taken":false,"tags":[1]}],"#,
+                r#""memory":{"40":[0],"41":[1],"42":[0],"43":[1]}}"#
+            ),
+            report.to_json()
+        );
+    }
+
+    #[test]
+    fn taint_overwrites() {
+        // in0 lands in 10, then a constant overwrites it
+        let prog = vec![3, 10, 1101, 1, 1, 10, 4, 10, 99];
+        let mut cpu = Cpu::new_detached(&prog);
+        assert!(cpu.taint_report().is_none());
+        cpu.track_taint();
+        cpu.push_input(5);
+        while cpu.step().unwrap() != Step::Halt {}
+
+        let report = cpu.taint_report().unwrap();
+        assert_eq!(vec![5], report.inputs);
+        assert!(report.outputs[0].tags.is_empty());
+        assert!(report.memory.is_empty());
+    }
+
+    #[test]
+    fn taint_immediate_writes() {
+        // writes in immediate mode land where position mode would, in0 goes
+        // to 10 and from there to 11
+        let prog = vec![103, 10, 11001, 10, 1, 11, 4, 11, 99];
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.track_taint();
+        cpu.push_input(5);
+        while cpu.step().unwrap() != Step::Halt {}
+
+        let report = cpu.taint_report().unwrap();
+        assert_eq!(vec![5], report.inputs);
+        assert_eq!(vec![&0], report.outputs[0].tags.iter().collect::<Vec<_>>());
+        assert_eq!(vec![&10, &11], report.memory.keys().collect::<Vec<_>>());
+    }
+}
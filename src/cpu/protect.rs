@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    // can be read and run but not written
+    ReadOnly,
+    // can only be run, reading or writing it as data is an error
+    ExecuteOnly,
+    // can be read and written but not run
+    NoExecute,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::ReadOnly => write!(f, "read-only"),
+            Access::ExecuteOnly => write!(f, "execute-only"),
+            Access::NoExecute => write!(f, "no-execute"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub start: u128,
+    // one past the last address
+    pub end: u128,
+    pub access: Access,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} region {}..{}", self.access, self.start, self.end)
+    }
+}
+
+// a write into code that already ran
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modification {
+    // instructions executed before the write
+    pub count: u64,
+    pub pc: u128,
+    pub addr: u128,
+    pub old: i128,
+    pub new: i128,
+}
+
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} wrote {} over {} at {}",
+            self.count, self.pc, self.new, self.old, self.addr
+        )
+    }
+}
+
+// The regions a cpu has to keep to and, when reporting, the cells of
+// every instruction it ran with the writes that landed on them since.
+#[derive(Debug, Clone, Default)]
+pub struct Protection {
+    pub regions: Vec<Region>,
+    executed: Option<HashSet<u128>>,
+    pub log: Vec<Modification>,
+}
+
+impl Protection {
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty() && self.executed.is_none()
+    }
+
+    pub fn report(&mut self) {
+        self.executed = Some(HashSet::new());
+        self.log.clear();
+    }
+
+    pub fn reporting(&self) -> bool {
+        self.executed.is_some()
+    }
+
+    // back to nothing ran, the regions stay
+    pub fn reset(&mut self) {
+        if self.executed.is_some() {
+            self.report();
+        }
+    }
+
+    // the first region with `access` that holds `addr`
+    pub fn find(&self, addr: u128, access: Access) -> Option<&Region> {
+        self.regions
+            .iter()
+            .find(|r| r.access == access && (r.start..r.end).contains(&addr))
+    }
+
+    pub fn executed(&self, addr: u128) -> bool {
+        match &self.executed {
+            Some(executed) => executed.contains(&addr),
+            None => false,
+        }
+    }
+
+    pub fn run(&mut self, pc: u128, len: u128) {
+        if let Some(executed) = self.executed.as_mut() {
+            executed.extend(pc..pc + len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod protect_tests {
+    use super::*;
+    use crate::cpu::Cpu;
+
+    fn run(prog: &[i128], region: Option<Region>) -> Result<Vec<i128>, String> {
+        let mut cpu = Cpu::new_detached(prog);
+        if let Some(r) = region {
+            cpu.protect(r.start, r.end, r.access);
+        }
+        cpu.into_outputs(vec![7])
+            .collect::<anyhow::Result<_>>()
+            .map_err(|e| e.to_string())
+    }
+
+    fn region(start: u128, end: u128, access: Access) -> Option<Region> {
+        Some(Region { start, end, access })
+    }
+
+    #[test]
+    fn regions() {
+        // [9] = 1 + 2, outputs [9] and [8]
+        let prog = vec![1101, 1, 2, 9, 4, 9, 4, 8, 99, 0];
+        assert_eq!(Ok(vec![3, 99]), run(&prog, None));
+        assert_eq!(Ok(vec![3, 99]), run(&prog, region(0, 9, Access::ReadOnly)));
+        assert_eq!(
+            Err("write to 9 in read-only region 5..10 at '0'".to_string()),
+            run(&prog, region(5, 10, Access::ReadOnly))
+        );
+        assert_eq!(
+            Err("write to 9 in execute-only region 9..10 at '0'".to_string()),
+            run(&prog, region(9, 10, Access::ExecuteOnly))
+        );
+        assert_eq!(
+            Err("read of 8 in execute-only region 0..9 at '6'".to_string()),
+            run(&prog, region(0, 9, Access::ExecuteOnly))
+        );
+        assert_eq!(
+            Err("execution in no-execute region 6..8 at '6'".to_string()),
+            run(&prog, region(6, 8, Access::NoExecute))
+        );
+        assert_eq!(
+            Ok(vec![3, 99]),
+            run(&prog, region(9, 10, Access::NoExecute))
+        );
+
+        // the input would land on the halt
+        let prog = vec![3, 2, 99];
+        assert_eq!(
+            Err("write to 2 in read-only region 0..3 at '0'".to_string()),
+            run(&prog, region(0, 3, Access::ReadOnly))
+        );
+    }
+
+    #[test]
+    fn self_modification() {
+        let prog = crate::cpu::parse_input("resources/day5-input.txt").unwrap();
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.report_self_modification();
+        let mut out = cpu.into_outputs(vec![5]);
+        assert_eq!(9386583, out.next().unwrap().unwrap());
+        let mut cpu = out.into_cpu();
+        assert_eq!(
+            vec![
+                Modification {
+                    count: 13,
+                    pc: 284,
+                    addr: 0,
+                    old: 3,
+                    new: 294
+                },
+                Modification {
+                    count: 17,
+                    pc: 304,
+                    addr: 0,
+                    old: 294,
+                    new: 314
+                },
+            ],
+            cpu.self_modifications().unwrap()
+        );
+        assert_eq!(
+            "13: 284 wrote 294 over 3 at 0",
+            cpu.self_modifications().unwrap()[0].to_string()
+        );
+
+        cpu.reset();
+        assert_eq!(Some(&[][..]), cpu.self_modifications());
+        let prog = crate::cpu::parse_input("resources/day9-input.txt").unwrap();
+        let mut cpu = Cpu::new_detached(&prog);
+        cpu.report_self_modification();
+        let mut out = cpu.into_outputs(vec![1]);
+        assert_eq!(3063082071, out.next().unwrap().unwrap());
+        assert!(out.into_cpu().self_modifications().unwrap().is_empty());
+    }
+}
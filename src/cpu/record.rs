@@ -0,0 +1,226 @@
+use super::{Cpu, Image, Step};
+use anyhow::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Input(i128),
+    Output(i128),
+}
+
+// The inputs and outputs of a run, each with the number of instructions
+// executed before it. As text, one event per line:
+//
+//     in 0 5
+//     out 12 7
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    pub events: Vec<(u64, Event)>,
+}
+
+impl Recording {
+    #[allow(dead_code)]
+    pub fn to_text(&self) -> String {
+        self.events
+            .iter()
+            .map(|(count, event)| match event {
+                Event::Input(v) => format!("in {} {}\n", count, v),
+                Event::Output(v) => format!("out {} {}\n", count, v),
+            })
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn parse(text: &str) -> Result<Recording> {
+        let mut events = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (kind, count, value) = match fields[..] {
+                [] => continue,
+                [kind, count, value] => (kind, count.parse()?, value.parse()?),
+                _ => anyhow::bail!("bad event '{}' on line {}", line, n + 1),
+            };
+            let event = match kind {
+                "in" => Event::Input(value),
+                "out" => Event::Output(value),
+                _ => anyhow::bail!("unknown event '{}' on line {}", kind, n + 1),
+            };
+            events.push((count, event));
+        }
+        Ok(Recording { events })
+    }
+}
+
+// The file `--record <path>` on the command line asks a run to be recorded
+// to. `start` it on the cpu before the run and `save` it after.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recorder {
+    path: String,
+}
+
+// the recorder for the arguments `[binary, "--record", path]`
+#[allow(dead_code)]
+pub fn from_args(args: &[String]) -> Option<Recorder> {
+    match args.get(1..)? {
+        [flag, path] if flag == "--record" => Some(Recorder { path: path.clone() }),
+        _ => None,
+    }
+}
+
+impl Recorder {
+    #[allow(dead_code)]
+    pub fn start(&self, cpu: &mut Cpu) {
+        cpu.record();
+    }
+
+    #[allow(dead_code)]
+    pub fn save(&self, cpu: &Cpu) -> Result<()> {
+        match cpu.recording() {
+            Some(recording) => Ok(std::fs::write(&self.path, recording.to_text())?),
+            None => anyhow::bail!("nothing recorded for '{}'", self.path),
+        }
+    }
+}
+
+// Runs the program on the recorded inputs and checks that every input is
+// read and every output written at the instruction it was recorded at.
+// The run stops when it wants an input past the recorded ones.
+#[allow(dead_code)]
+pub fn replay(program: impl Into<Image>, recording: &Recording) -> Result<Recording> {
+    let mut cpu = Cpu::new_detached(program);
+    cpu.record();
+    let mut inputs = recording
+        .events
+        .iter()
+        .filter_map(|(_, event)| match event {
+            Event::Input(v) => Some(*v),
+            _ => None,
+        });
+
+    loop {
+        let step = cpu.step()?;
+        let replayed = &cpu.recording.as_ref().unwrap().events;
+        if let Some(last) = replayed.last() {
+            let n = replayed.len() - 1;
+            match recording.events.get(n) {
+                Some(recorded) if recorded == last => {}
+                Some(recorded) => anyhow::bail!(
+                    "event {} was {:?} at {}, recorded {:?} at {}",
+                    n,
+                    last.1,
+                    last.0,
+                    recorded.1,
+                    recorded.0
+                ),
+                None => anyhow::bail!("event {} {:?} at {} wasn't recorded", n, last.1, last.0),
+            }
+        }
+
+        match step {
+            Step::NeedInput => match inputs.next() {
+                Some(v) => cpu.push_input(v),
+                None => break,
+            },
+            Step::Halt => break,
+            _ => {}
+        }
+    }
+
+    let replayed = cpu.recording.unwrap();
+    if replayed.events.len() < recording.events.len() {
+        anyhow::bail!(
+            "the run ended after {} of {} events",
+            replayed.events.len(),
+            recording.events.len()
+        );
+    }
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod record_tests {
+    use super::*;
+
+    // outputs twice each input until it reads a 0
+    fn doubler() -> Vec<i128> {
+        vec![
+            3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+        ]
+    }
+
+    fn record(prog: &[i128], inputs: Vec<i128>) -> Recording {
+        let mut cpu = Cpu::new_detached(prog);
+        cpu.record();
+        let mut out = cpu.into_outputs(inputs);
+        out.by_ref().for_each(|v| assert!(v.is_ok()));
+        out.into_cpu().recording().unwrap().clone()
+    }
+
+    #[test]
+    fn round_trip() {
+        let recording = record(&doubler(), vec![4, 5, 0]);
+        assert_eq!(
+            "in 0 4\nout 3 8\nin 5 5\nout 8 10\nin 10 0\n",
+            recording.to_text()
+        );
+        assert_eq!(recording, Recording::parse(&recording.to_text()).unwrap());
+        assert_eq!(recording, replay(&doubler(), &recording).unwrap());
+        assert!(Recording::parse("in 3").is_err());
+    }
+
+    #[test]
+    fn mismatches() {
+        let recording = record(&doubler(), vec![4, 0]);
+
+        let mut wrong = recording.clone();
+        wrong.events[1] = (3, Event::Output(9));
+        let err = replay(&doubler(), &wrong).unwrap_err();
+        assert_eq!(
+            "event 1 was Output(8) at 3, recorded Output(9) at 3",
+            err.to_string()
+        );
+
+        let mut late = recording.clone();
+        late.events[0].0 = 1;
+        assert!(replay(&doubler(), &late).is_err());
+
+        // triples instead
+        let mut prog = doubler();
+        prog[7] = 3;
+        assert!(replay(&prog, &recording).is_err());
+    }
+
+    #[test]
+    fn recorder() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+        assert!(from_args(&args(&["day13"])).is_none());
+        assert!(from_args(&args(&["day13", "--record"])).is_none());
+        assert!(from_args(&args(&["day13", "--scan", "x"])).is_none());
+        assert!(from_args(&[]).is_none());
+
+        let path = std::env::temp_dir().join("intcode-recorder-test.log");
+        let recorder = from_args(&args(&["day13", "--record", path.to_str().unwrap()])).unwrap();
+        let mut cpu = Cpu::new_detached(&doubler());
+        assert!(recorder.save(&cpu).is_err());
+
+        recorder.start(&mut cpu);
+        let mut out = cpu.into_outputs(vec![4, 0]);
+        out.by_ref().for_each(|v| assert!(v.is_ok()));
+        recorder.save(&out.into_cpu()).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!("in 0 4\nout 3 8\nin 5 0\n", text);
+    }
+
+    #[test]
+    fn ascii_script() {
+        let prog = crate::cpu::parse_input("resources/day21-input.txt").unwrap();
+        let script = b"NOT C J\nNOT A T\nOR T J\nAND D J\nWALK\n";
+        let recording = record(&prog, script.iter().map(|b| *b as i128).collect());
+
+        let replayed = replay(&prog, &recording).unwrap();
+        assert_eq!(
+            Some(&Event::Output(19355364)),
+            replayed.events.last().map(|e| &e.1)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use super::Cpu;
+use std::collections::BTreeMap;
+
+// how a cell changed since the last snapshot
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Relation {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(i128),
+}
+
+impl Relation {
+    #[allow(dead_code)]
+    fn holds(&self, before: i128, now: i128) -> bool {
+        match self {
+            Relation::Changed => now != before,
+            Relation::Unchanged => now == before,
+            Relation::Increased => now > before,
+            Relation::Decreased => now < before,
+            Relation::Equals(v) => now == *v,
+        }
+    }
+}
+
+// Narrows down where a program keeps a value. Every cell starts as a
+// candidate, each snapshot of the cpu drops the ones whose change doesn't
+// match. A cell the cpu only has from a later snapshot on read as 0 in the
+// ones before, and is a candidate if that matches every relation so far.
+#[allow(dead_code)]
+pub struct Scanner {
+    last: BTreeMap<u128, i128>,
+    candidates: Vec<u128>,
+    relations: Vec<Relation>,
+}
+
+impl Scanner {
+    #[allow(dead_code)]
+    pub fn new(cpu: &Cpu) -> Scanner {
+        let last = cpu.mem.snapshot();
+        let candidates = last.keys().copied().collect();
+        Scanner {
+            last,
+            candidates,
+            relations: vec![],
+        }
+    }
+
+    // the number of candidates left
+    #[allow(dead_code)]
+    pub fn narrow(&mut self, cpu: &Cpu, relation: Relation) -> usize {
+        let unseen = self.relations.iter().all(|r| r.holds(0, 0));
+        for k in cpu.mem.snapshot().keys() {
+            if !self.last.contains_key(k) {
+                self.last.insert(*k, 0);
+                if unseen {
+                    self.candidates.push(*k);
+                }
+            }
+        }
+        self.candidates.sort_unstable();
+        self.relations.push(relation);
+
+        let last = &self.last;
+        self.candidates
+            .retain(|k| relation.holds(last[k], cpu.mem.get(*k)));
+        for k in self.candidates.iter() {
+            self.last.insert(*k, cpu.mem.get(*k));
+        }
+        self.candidates.len()
+    }
+
+    // candidates and their values at the last snapshot
+    #[allow(dead_code)]
+    pub fn candidates(&self) -> Vec<(u128, i128)> {
+        self.candidates.iter().map(|k| (*k, self.last[k])).collect()
+    }
+}
+
+#[cfg(test)]
+mod scan_tests {
+    use super::*;
+    use crate::cpu::Step;
+
+    // counts up in 20 and down in 21, reading an input between steps
+    fn counter() -> Cpu {
+        let prog = vec![
+            1001, 20, 1, 20, 1001, 21, -1, 21, 3, 22, 1105, 1, 0, 0, 0, 0, 0, 0, 0, 0, 5, 5, 0,
+        ];
+        Cpu::new_detached(&prog)
+    }
+
+    fn frame(cpu: &mut Cpu, input: i128) {
+        while cpu.step().unwrap() != Step::NeedInput {}
+        cpu.push_input(input);
+    }
+
+    #[test]
+    fn narrows() {
+        let mut cpu = counter();
+        let mut scanner = Scanner::new(&cpu);
+        frame(&mut cpu, 5);
+
+        scanner.narrow(&cpu, Relation::Changed);
+        assert_eq!(vec![(20, 6), (21, 4)], scanner.candidates());
+        frame(&mut cpu, 5);
+        assert_eq!(1, scanner.narrow(&cpu, Relation::Increased));
+        assert_eq!(vec![(20, 7)], scanner.candidates());
+
+        let mut scanner = Scanner::new(&cpu);
+        frame(&mut cpu, 9);
+        scanner.narrow(&cpu, Relation::Equals(5));
+        // the input pushed the frame before landed in 22
+        assert_eq!(vec![(22, 5)], scanner.candidates());
+    }
+
+    #[test]
+    fn cells_from_later_snapshots() {
+        // writes 7 to 5000 on the first frame, then counts it up
+        let prog = vec![
+            1101, 0, 7, 5000, 3, 20, 1001, 5000, 1, 5000, 3, 20, 1105, 1, 6,
+        ];
+        let mut cpu = Cpu::new_detached(&prog);
+        let mut scanner = Scanner::new(&cpu);
+        frame(&mut cpu, 0);
+
+        scanner.narrow(&cpu, Relation::Changed);
+        assert_eq!(vec![(5000, 7)], scanner.candidates());
+        frame(&mut cpu, 0);
+        assert_eq!(1, scanner.narrow(&cpu, Relation::Increased));
+        assert_eq!(vec![(5000, 8)], scanner.candidates());
+
+        // 5000 was still 0 when nothing was supposed to change
+        let mut cpu = Cpu::new_detached(&prog);
+        let mut scanner = Scanner::new(&cpu);
+        assert_eq!(0, scanner.narrow(&cpu, Relation::Changed));
+        frame(&mut cpu, 0);
+        assert_eq!(0, scanner.narrow(&cpu, Relation::Changed));
+    }
+
+    #[test]
+    fn pins() {
+        let mut cpu = counter();
+        cpu.pin(20, 100);
+        frame(&mut cpu, 0);
+        frame(&mut cpu, 0);
+        assert_eq!(100, cpu.get_mem(20).unwrap());
+        assert_eq!(3, cpu.get_mem(21).unwrap());
+
+        // a reset keeps the pins
+        cpu.reset();
+        assert_eq!(100, cpu.get_mem(20).unwrap());
+        frame(&mut cpu, 0);
+        assert_eq!(100, cpu.get_mem(20).unwrap());
+
+        cpu.unpin(20);
+        frame(&mut cpu, 0);
+        assert_eq!(101, cpu.get_mem(20).unwrap());
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// indexes of the inputs a value was computed from
+pub type Tags = BTreeSet<usize>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Output {
+    pub value: i128,
+    pub tags: Tags,
+}
+
+// a conditional jump that depended on the inputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jump {
+    pub pc: u128,
+    pub taken: bool,
+    pub tags: Tags,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub inputs: Vec<i128>,
+    pub outputs: Vec<Output>,
+    pub jumps: Vec<Jump>,
+    pub memory: BTreeMap<u128, Tags>,
+}
+
+fn tags_json(tags: &Tags) -> String {
+    let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+    format!("[{}]", tags.join(","))
+}
+
+impl Report {
+    #[allow(dead_code)]
+    pub fn to_json(&self) -> String {
+        let inputs: Vec<String> = self.inputs.iter().map(|v| v.to_string()).collect();
+        let outputs: Vec<String> = self
+            .outputs
+            .iter()
+            .map(|o| format!("{{\"value\":{},\"tags\":{}}}", o.value, tags_json(&o.tags)))
+            .collect();
+        let jumps: Vec<String> = self
+            .jumps
+            .iter()
+            .map(|j| {
+                format!(
+                    "{{\"pc\":{},\"taken\":{},\"tags\":{}}}",
+                    j.pc,
+                    j.taken,
+                    tags_json(&j.tags)
+                )
+            })
+            .collect();
+        let memory: Vec<String> = self
+            .memory
+            .iter()
+            .map(|(addr, tags)| format!("\"{}\":{}", addr, tags_json(tags)))
+            .collect();
+
+        format!(
+            "{{\"inputs\":[{}],\"outputs\":[{}],\"jumps\":[{}],\"memory\":{{{}}}}}",
+            inputs.join(","),
+            outputs.join(","),
+            jumps.join(","),
+            memory.join(",")
+        )
+    }
+}
+
+// Shadow memory holding the tags of every tainted cell. Untainted cells
+// are not stored, writing a value without tags clears the cell.
+#[derive(Debug, Clone, Default)]
+pub struct Taint {
+    shadow: HashMap<u128, Tags>,
+    report: Report,
+}
+
+impl Taint {
+    // immediate parameters (`None`) are never tainted
+    pub fn tags(&self, addr: Option<u128>) -> Tags {
+        addr.and_then(|addr| self.shadow.get(&addr))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn write(&mut self, addr: u128, tags: Tags) {
+        if tags.is_empty() {
+            self.shadow.remove(&addr);
+        } else {
+            self.shadow.insert(addr, tags);
+        }
+    }
+
+    pub fn input(&mut self, addr: u128, value: i128) {
+        let tag = self.report.inputs.len();
+        self.report.inputs.push(value);
+        self.write(addr, Some(tag).into_iter().collect());
+    }
+
+    pub fn output(&mut self, value: i128, tags: Tags) {
+        self.report.outputs.push(Output { value, tags });
+    }
+
+    pub fn jump(&mut self, pc: u128, taken: bool, tags: Tags) {
+        if !tags.is_empty() {
+            self.report.jumps.push(Jump { pc, taken, tags });
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        let mut report = self.report.clone();
+        report.memory = self
+            .shadow
+            .iter()
+            .map(|(addr, tags)| (*addr, tags.clone()))
+            .collect();
+        report
+    }
+}
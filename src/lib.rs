@@ -0,0 +1,225 @@
+// The intcode cpu, which every binary uses from here, and a C library around
+// it, see include/intcode.h. Values cross the boundary as 64 bit integers,
+// cells that don't fit can't be read.
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+
+pub mod cpu;
+use cpu::{Cpu, Step};
+
+pub const INTCODE_HALTED: c_int = 0;
+pub const INTCODE_NEEDS_INPUT: c_int = 1;
+pub const INTCODE_ERROR: c_int = -1;
+
+// the opaque handle
+pub struct Intcode {
+    cpu: Cpu,
+    outputs: VecDeque<i128>,
+    error: Option<CString>,
+}
+
+/// A machine running a copy of the `len` cells at `program`, or null when
+/// `program` is null.
+///
+/// # Safety
+/// `program` has to point to `len` readable values.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_new(program: *const i64, len: usize) -> *mut Intcode {
+    if program.is_null() {
+        return std::ptr::null_mut();
+    }
+    let program: Vec<i128> = std::slice::from_raw_parts(program, len)
+        .iter()
+        .map(|v| *v as i128)
+        .collect();
+    Box::into_raw(Box::new(Intcode {
+        cpu: Cpu::new_detached(&program),
+        outputs: VecDeque::new(),
+        error: None,
+    }))
+}
+
+/// # Safety
+/// `machine` has to come from `intcode_new` and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_push_input(machine: *mut Intcode, value: i64) {
+    (*machine).cpu.push_input(value as i128);
+}
+
+/// Runs until the program halts, wants an input nobody pushed or fails.
+/// Returns `INTCODE_HALTED`, `INTCODE_NEEDS_INPUT` or `INTCODE_ERROR`. A
+/// panic in the cpu, like an overflow in a debug build, is an error too.
+///
+/// # Safety
+/// `machine` has to come from `intcode_new` and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_run(machine: *mut Intcode) -> c_int {
+    let machine = &mut *machine;
+    machine.error = None;
+    let (cpu, outputs) = (&mut machine.cpu, &mut machine.outputs);
+    // a panic can't unwind into the caller
+    let run = panic::catch_unwind(AssertUnwindSafe(|| loop {
+        match cpu.step() {
+            Ok(Step::Continue) => {}
+            Ok(Step::Output(v)) => outputs.push_back(v),
+            Ok(Step::NeedInput) => return Ok(INTCODE_NEEDS_INPUT),
+            Ok(Step::Halt) => return Ok(INTCODE_HALTED),
+            Err(e) => return Err(e.to_string()),
+        }
+    }));
+    let message = match run {
+        Ok(Ok(status)) => return status,
+        Ok(Err(message)) => message,
+        Err(payload) => match payload.downcast_ref::<&str>() {
+            Some(message) => format!("panic: {}", message),
+            None => "panic".to_string(),
+        },
+    };
+    // an error message has no nul bytes
+    machine.error = CString::new(message).ok();
+    INTCODE_ERROR
+}
+
+/// Takes the oldest output into `value`. Returns 0 when there is none, -1
+/// when it doesn't fit in 64 bits and 1 otherwise.
+///
+/// # Safety
+/// `machine` has to come from `intcode_new` and not be freed, `value` has
+/// to be writable.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_pop_output(machine: *mut Intcode, value: *mut i64) -> c_int {
+    let machine = &mut *machine;
+    match machine.outputs.front() {
+        None => 0,
+        Some(v) if *v < i64::MIN as i128 || *v > i64::MAX as i128 => -1,
+        Some(_) => {
+            *value = machine.outputs.pop_front().unwrap() as i64;
+            1
+        }
+    }
+}
+
+/// Reads the cell at `addr` into `value`. Returns -1 when it doesn't fit in
+/// 64 bits and 0 otherwise.
+///
+/// # Safety
+/// `machine` has to come from `intcode_new` and not be freed, `value` has
+/// to be writable.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_read(
+    machine: *const Intcode,
+    addr: u64,
+    value: *mut i64,
+) -> c_int {
+    let v = (*machine).cpu.get_mem(addr as u128).unwrap_or(0);
+    if v < i64::MIN as i128 || v > i64::MAX as i128 {
+        return -1;
+    }
+    *value = v as i64;
+    0
+}
+
+/// # Safety
+/// `machine` has to come from `intcode_new` and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_write(machine: *mut Intcode, addr: u64, value: i64) {
+    (*machine).cpu.set_mem(addr as u128, value as i128);
+}
+
+/// The message of the last error, null before any. It lives until the next
+/// `intcode_run` or `intcode_free`.
+///
+/// # Safety
+/// `machine` has to come from `intcode_new` and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_error(machine: *const Intcode) -> *const c_char {
+    match &(*machine).error {
+        Some(e) => e.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// # Safety
+/// `machine` has to come from `intcode_new` or be null, and is gone after.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_free(machine: *mut Intcode) {
+    if !machine.is_null() {
+        drop(Box::from_raw(machine));
+    }
+}
+
+#[cfg(test)]
+mod ffi_tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn round_trip() {
+        // outputs twice each input until it reads a 0
+        let prog: Vec<i64> = vec![
+            3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1105, 1, 0, 99, 0,
+        ];
+        unsafe {
+            assert!(intcode_new(std::ptr::null(), 3).is_null());
+            let machine = intcode_new(prog.as_ptr(), prog.len());
+            let mut v = 0;
+            assert_eq!(INTCODE_NEEDS_INPUT, intcode_run(machine));
+            assert_eq!(0, intcode_pop_output(machine, &mut v));
+
+            intcode_push_input(machine, 21);
+            intcode_push_input(machine, 4);
+            assert_eq!(INTCODE_NEEDS_INPUT, intcode_run(machine));
+            assert_eq!(1, intcode_pop_output(machine, &mut v));
+            assert_eq!(42, v);
+            assert_eq!(1, intcode_pop_output(machine, &mut v));
+            assert_eq!(8, v);
+
+            // triples from now on
+            intcode_write(machine, 7, 3);
+            assert_eq!(0, intcode_read(machine, 7, &mut v));
+            assert_eq!(3, v);
+            intcode_push_input(machine, 5);
+            intcode_push_input(machine, 0);
+            assert_eq!(INTCODE_HALTED, intcode_run(machine));
+            assert_eq!(1, intcode_pop_output(machine, &mut v));
+            assert_eq!(15, v);
+
+            assert!(intcode_error(machine).is_null());
+            // the halt becomes garbage
+            intcode_write(machine, 14, 98);
+            assert_eq!(INTCODE_ERROR, intcode_run(machine));
+            let error = CStr::from_ptr(intcode_error(machine)).to_str().unwrap();
+            assert_eq!("unknown opcode '98' at '14'", error);
+
+            // a clean run clears the error
+            intcode_write(machine, 14, 99);
+            assert_eq!(INTCODE_HALTED, intcode_run(machine));
+            assert!(intcode_error(machine).is_null());
+
+            intcode_write(machine, 20, i64::MAX);
+            let big = (*machine).cpu.get_mem(20).unwrap() * 4;
+            (*machine).cpu.set_mem(20, big);
+            assert_eq!(-1, intcode_read(machine, 20, &mut v));
+            intcode_free(machine);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        // squares [7] until it overflows, which only panics with debug
+        // assertions
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let prog: Vec<i64> = vec![2, 7, 7, 7, 1105, 1, 0, 3037000499];
+        unsafe {
+            let machine = intcode_new(prog.as_ptr(), prog.len());
+            assert_eq!(INTCODE_ERROR, intcode_run(machine));
+            let error = CStr::from_ptr(intcode_error(machine)).to_str().unwrap();
+            assert_eq!("panic: attempt to multiply with overflow", error);
+            intcode_free(machine);
+        }
+    }
+}